@@ -61,6 +61,26 @@ SELECT typeof(geom) FROM read_parquet('$addressesPath') LIMIT 1;
 $addressesGeomType = ($detectSqlAddresses | & $DuckdbExe ":memory:" -csv -noheader).Trim()
 if ($LASTEXITCODE -ne 0) { throw "DuckDB detect failed (addresses)" }
 
+# Working CRS of the department (see src/crs.rs)
+$workingCrs = switch ($Dept) {
+    { $_ -in "971", "972" } { "EPSG:5490" }
+    "973" { "EPSG:2972" }
+    "974" { "EPSG:2975" }
+    "976" { "EPSG:4471" }
+    default { "EPSG:2154" }
+}
+
+# Matches written before candidate mode have no rank column (all rows are rank 1)
+$detectSqlRank = @"
+SELECT count(*) FROM parquet_schema('$matchesPath') WHERE name = 'rank';
+"@
+$matchesHasRank = ($detectSqlRank | & $DuckdbExe ":memory:" -csv -noheader).Trim()
+if ($LASTEXITCODE -ne 0) { throw "DuckDB detect failed (matches)" }
+$matchesRankFilter = "WHERE rank = 1"
+if ($matchesHasRank -eq "0") {
+    $matchesRankFilter = ""
+}
+
 $parcelsGeomExpr = "ST_GeomFromWKB(geom)"
 if ($parcelsGeomType -like "*GEOMETRY*") {
     $parcelsGeomExpr = "geom"
@@ -75,7 +95,8 @@ $sql = @"
 INSTALL spatial; LOAD spatial;
 
 CREATE OR REPLACE VIEW matches AS
-SELECT * FROM read_parquet('$matchesPath');
+SELECT * FROM read_parquet('$matchesPath')
+$matchesRankFilter;
 
 CREATE OR REPLACE VIEW parcels AS
 SELECT
@@ -97,6 +118,7 @@ CREATE OR REPLACE MACRO match_prio(mt) AS (
     WHEN 'Inside'          THEN 1
     WHEN 'BorderNear'      THEN 2
     WHEN 'FallbackNearest' THEN 3
+    WHEN 'PreExistingFar'  THEN 4
     ELSE 100
   END
 );
@@ -167,8 +189,8 @@ COPY (
   SELECT
     a.id AS id_ban,
     a.code_insee,
-    ST_X(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS lon,
-    ST_Y(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS lat,
+    ST_X(ST_Transform(a.geom, '$workingCrs', 'OGC:CRS84')) AS lon,
+    ST_Y(ST_Transform(a.geom, '$workingCrs', 'OGC:CRS84')) AS lat,
     b.id_parcelle,
     b.match_type,
     b.distance_m,
@@ -187,7 +209,7 @@ COPY (
     b.distance_m,
     b.confidence,
     parcel_band(b.match_type, b.distance_m) AS parcel_class,
-    ST_AsGeoJSON(ST_Transform(p.geom, 'EPSG:2154', 'OGC:CRS84')) AS geometry
+    ST_AsGeoJSON(ST_Transform(p.geom, '$workingCrs', 'OGC:CRS84')) AS geometry
   FROM parcels p
   LEFT JOIN best_match_parcel b ON p.id = b.id_parcelle
 ) TO '$parcOut' (FORMAT 'CSV', HEADER);
@@ -201,10 +223,10 @@ COPY (
     b.distance_m,
     b.confidence,
     addr_band(b.match_type, b.distance_m) AS class_match,
-    ST_X(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lon,
-    ST_Y(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lat,
-    ST_X(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lon,
-    ST_Y(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lat
+    ST_X(ST_Transform(a.geom, '$workingCrs', 'OGC:CRS84')) AS addr_lon,
+    ST_Y(ST_Transform(a.geom, '$workingCrs', 'OGC:CRS84')) AS addr_lat,
+    ST_X(ST_Transform(ST_Centroid(p.geom), '$workingCrs', 'OGC:CRS84')) AS parc_lon,
+    ST_Y(ST_Transform(ST_Centroid(p.geom), '$workingCrs', 'OGC:CRS84')) AS parc_lat
   FROM best_match_address b
   JOIN addresses a ON a.id = b.id_ban
   JOIN parcels   p ON p.id = b.id_parcelle
@@ -219,10 +241,10 @@ COPY (
     b.distance_m,
     b.confidence,
     parcel_band(b.match_type, b.distance_m) AS class_match,
-    ST_X(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lon,
-    ST_Y(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lat,
-    ST_X(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lon,
-    ST_Y(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lat
+    ST_X(ST_Transform(ST_Centroid(p.geom), '$workingCrs', 'OGC:CRS84')) AS parc_lon,
+    ST_Y(ST_Transform(ST_Centroid(p.geom), '$workingCrs', 'OGC:CRS84')) AS parc_lat,
+    ST_X(ST_Transform(a.geom, '$workingCrs', 'OGC:CRS84')) AS addr_lon,
+    ST_Y(ST_Transform(a.geom, '$workingCrs', 'OGC:CRS84')) AS addr_lat
   FROM best_match_parcel b
   JOIN parcels   p ON p.id = b.id_parcelle
   JOIN addresses a ON a.id = b.id_ban
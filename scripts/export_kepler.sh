@@ -60,6 +60,23 @@ SELECT typeof(geom) FROM read_parquet('${addresses_sql}') LIMIT 1;
 SQL
 )"
 
+# Working CRS of the department (see src/crs.rs)
+case "$DEPT" in
+  971|972) working_crs="EPSG:5490" ;;
+  973)     working_crs="EPSG:2972" ;;
+  974)     working_crs="EPSG:2975" ;;
+  976)     working_crs="EPSG:4471" ;;
+  *)       working_crs="EPSG:2154" ;;
+esac
+
+# Matches written before candidate mode have no rank column (all rows are rank 1)
+matches_has_rank="$("$DUCKDB_EXE" ":memory:" -csv -noheader <<SQL
+SELECT count(*) FROM parquet_schema('${matches_sql}') WHERE name = 'rank';
+SQL
+)"
+matches_rank_filter="WHERE rank = 1"
+[[ "$matches_has_rank" == 0 ]] && matches_rank_filter=""
+
 parcels_geom_expr="ST_GeomFromWKB(geom)"
 addresses_geom_expr="ST_GeomFromWKB(geom)"
 [[ "$parcels_geom_type" == *GEOMETRY* ]]   && parcels_geom_expr="geom"
@@ -69,7 +86,8 @@ addresses_geom_expr="ST_GeomFromWKB(geom)"
 INSTALL spatial; LOAD spatial;
 
 CREATE OR REPLACE VIEW matches AS
-SELECT * FROM read_parquet('${matches_sql}');
+SELECT * FROM read_parquet('${matches_sql}')
+${matches_rank_filter};
 
 CREATE OR REPLACE VIEW parcels AS
 SELECT id, code_insee, ${parcels_geom_expr} AS geom
@@ -85,6 +103,7 @@ CREATE OR REPLACE MACRO match_prio(mt) AS (
     WHEN 'Inside'          THEN 1
     WHEN 'BorderNear'      THEN 2
     WHEN 'FallbackNearest' THEN 3
+    WHEN 'PreExistingFar'  THEN 4
     ELSE 100
   END
 );
@@ -147,8 +166,8 @@ COPY (
   SELECT
     a.id AS id_ban,
     a.code_insee,
-    ST_X(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS lon,
-    ST_Y(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS lat,
+    ST_X(ST_Transform(a.geom, '${working_crs}', 'OGC:CRS84')) AS lon,
+    ST_Y(ST_Transform(a.geom, '${working_crs}', 'OGC:CRS84')) AS lat,
     b.id_parcelle,
     b.match_type,
     b.distance_m,
@@ -167,7 +186,7 @@ COPY (
     b.distance_m,
     b.confidence,
     parcel_band(b.match_type, b.distance_m) AS parcel_class,
-    ST_AsGeoJSON(ST_Transform(p.geom, 'EPSG:2154', 'OGC:CRS84')) AS geometry
+    ST_AsGeoJSON(ST_Transform(p.geom, '${working_crs}', 'OGC:CRS84')) AS geometry
   FROM parcels p
   LEFT JOIN best_match_parcel b ON p.id = b.id_parcelle
 ) TO '${parc_out}' (FORMAT 'CSV', HEADER);
@@ -181,10 +200,10 @@ COPY (
     b.distance_m,
     b.confidence,
     addr_band(b.match_type, b.distance_m) AS class_match,
-    ST_X(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lon,
-    ST_Y(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lat,
-    ST_X(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lon,
-    ST_Y(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lat
+    ST_X(ST_Transform(a.geom, '${working_crs}', 'OGC:CRS84')) AS addr_lon,
+    ST_Y(ST_Transform(a.geom, '${working_crs}', 'OGC:CRS84')) AS addr_lat,
+    ST_X(ST_Transform(ST_Centroid(p.geom), '${working_crs}', 'OGC:CRS84')) AS parc_lon,
+    ST_Y(ST_Transform(ST_Centroid(p.geom), '${working_crs}', 'OGC:CRS84')) AS parc_lat
   FROM best_match_address b
   JOIN addresses a ON a.id = b.id_ban
   JOIN parcels   p ON p.id = b.id_parcelle
@@ -199,10 +218,10 @@ COPY (
     b.distance_m,
     b.confidence,
     parcel_band(b.match_type, b.distance_m) AS class_match,
-    ST_X(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lon,
-    ST_Y(ST_Transform(ST_Centroid(p.geom), 'EPSG:2154', 'OGC:CRS84')) AS parc_lat,
-    ST_X(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lon,
-    ST_Y(ST_Transform(a.geom, 'EPSG:2154', 'OGC:CRS84')) AS addr_lat
+    ST_X(ST_Transform(ST_Centroid(p.geom), '${working_crs}', 'OGC:CRS84')) AS parc_lon,
+    ST_Y(ST_Transform(ST_Centroid(p.geom), '${working_crs}', 'OGC:CRS84')) AS parc_lat,
+    ST_X(ST_Transform(a.geom, '${working_crs}', 'OGC:CRS84')) AS addr_lon,
+    ST_Y(ST_Transform(a.geom, '${working_crs}', 'OGC:CRS84')) AS addr_lat
   FROM best_match_parcel b
   JOIN parcels   p ON p.id = b.id_parcelle
   JOIN addresses a ON a.id = b.id_ban
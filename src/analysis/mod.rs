@@ -193,8 +193,14 @@ pub fn run_analyze(args: AnalyzeArgs) -> Result<AnalyzeOutcome> {
 
         // 2) best match per parcel
         let mp = matches_file.to_string_lossy();
-        let matches_lf =
+        let mut matches_lf =
             LazyFrame::scan_parquet(PlPath::from_str(&mp), ScanArgsParquet::default())?;
+        // Matches written before candidate mode have no `rank` column (all rows are rank 1).
+        let rank_filter = if matches_lf.collect_schema()?.contains("rank") {
+            col("rank").eq(lit(1u32))
+        } else {
+            lit(true)
+        };
         let priority_expr = when(col("match_type").eq(lit("PreExisting")))
             .then(lit(0))
             .otherwise(
@@ -206,7 +212,11 @@ pub fn run_analyze(args: AnalyzeArgs) -> Result<AnalyzeOutcome> {
                             .otherwise(
                                 when(col("match_type").eq(lit("FallbackNearest")))
                                     .then(lit(3))
-                                    .otherwise(lit(100)),
+                                    .otherwise(
+                                        when(col("match_type").eq(lit("PreExistingFar")))
+                                            .then(lit(4))
+                                            .otherwise(lit(100)),
+                                    ),
                             ),
                     ),
             );
@@ -214,7 +224,7 @@ pub fn run_analyze(args: AnalyzeArgs) -> Result<AnalyzeOutcome> {
         let selector = col("id_parcelle").into_selector();
 
         let best_df = matches_lf
-            .filter(col("id_parcelle").is_not_null())
+            .filter(col("id_parcelle").is_not_null().and(rank_filter))
             .select([
                 col("id_parcelle"),
                 col("id_ban"),
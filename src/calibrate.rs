@@ -0,0 +1,150 @@
+use crate::cli::CalibrateArgs;
+use anyhow::{anyhow, Result};
+use ban_cadastre::confidence::{
+    fit_logistic, ConfidenceModel, LogisticCoefficients, DENSITY_RADIUS_M, FEATURE_NAMES,
+};
+use ban_cadastre::indexer::AddressIndex;
+use ban_cadastre::loader::{load_addresses, load_parcels};
+use ban_cadastre::matcher::{
+    confidence_features, match_parcels_and_addresses_3_steps, split_links,
+};
+use ban_cadastre::parcel_id::ParcelIdResolver;
+use ban_cadastre::structures::{MatchConfig, MatchType, ParcelData};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Match types fitted from the links (PreExisting keeps the fixed table).
+const FITTED_TYPES: [MatchType; 3] = [
+    MatchType::Inside,
+    MatchType::BorderNear,
+    MatchType::FallbackNearest,
+];
+
+/// Below this many rows (or with a single class) a type keeps the fixed table.
+const MIN_SAMPLES: usize = 30;
+
+pub fn run_calibrate(args: CalibrateArgs) -> Result<()> {
+    info!(
+        input_addresses=?args.input_adresses,
+        input_parcels=?args.input_parcelles,
+        output=?args.output,
+        "starting calibrate mode"
+    );
+
+    let start_load = Instant::now();
+    let parcels = load_parcels(&args.input_parcelles)?;
+    let mut addresses = load_addresses(&args.input_adresses)?;
+    info!(
+        parcels = parcels.len(),
+        addresses = addresses.len(),
+        elapsed_ms = start_load.elapsed().as_millis(),
+        "loaded inputs"
+    );
+
+    // Every resolvable link becomes ground truth; links are hidden so the geometric steps run.
+    let known_parcels: HashMap<&str, &ParcelData> =
+        parcels.iter().map(|p| (p.id.as_str(), p)).collect();
+    let resolver = ParcelIdResolver::new(known_parcels.keys().copied());
+    let mut truth: HashMap<String, HashSet<String>> = HashMap::new();
+    for addr in addresses.iter_mut() {
+        let Some(links) = addr.existing_link.take() else {
+            continue;
+        };
+        let pids: HashSet<String> = split_links(&links)
+            .filter_map(|link| resolver.resolve(link))
+            .map(|r| r.id().to_owned())
+            .collect();
+        if !pids.is_empty() {
+            truth.insert(addr.id.clone(), pids);
+        }
+    }
+    info!(truth_addresses = truth.len(), "ground truth collected");
+    if truth.is_empty() {
+        return Err(anyhow!(
+            "no existing_link resolves to a known parcel; nothing to fit"
+        ));
+    }
+
+    // Best candidate per address only (as in production): Step 2 alternatives carry no
+    // runner-up, so their margin features would not match those of a retained link.
+    let mut config = MatchConfig {
+        address_max_distance_m: args.distance_threshold,
+        ..MatchConfig::default()
+    };
+    args.cross_commune.apply(&mut config);
+    args.positions.apply(&mut config);
+
+    info!(?config, "running matcher");
+    let start_match = Instant::now();
+    let matches: Vec<_> = match_parcels_and_addresses_3_steps(&parcels, &addresses, None, &config)
+        .into_iter()
+        .filter(|m| {
+            m.rank == 1 && truth.contains_key(&m.id_ban) && FITTED_TYPES.contains(&m.match_type)
+        })
+        .collect();
+    info!(
+        elapsed_ms = start_match.elapsed().as_millis(),
+        training_rows = matches.len(),
+        "matching completed"
+    );
+
+    let address_index = AddressIndex::build(&addresses);
+    let features = confidence_features(&matches, &known_parcels, &addresses, &address_index);
+
+    let mut samples: HashMap<&'static str, Vec<([f64; FEATURE_NAMES.len()], bool)>> =
+        HashMap::new();
+    for (m, f) in matches.iter().zip(&features) {
+        let (Some(f), Some(pid)) = (f, &m.id_parcelle) else {
+            continue;
+        };
+        let label = truth[&m.id_ban].contains(pid);
+        samples
+            .entry(m.match_type.as_str())
+            .or_default()
+            .push((f.to_vector(), label));
+    }
+
+    let mut by_match_type = HashMap::new();
+    for mt in FITTED_TYPES {
+        let rows = samples.remove(mt.as_str()).unwrap_or_default();
+        let positives = rows.iter().filter(|(_, y)| *y).count();
+        if rows.len() < MIN_SAMPLES || positives == 0 || positives == rows.len() {
+            warn!(
+                match_type = mt.as_str(),
+                samples = rows.len(),
+                positives,
+                "not enough labelled rows; keeping fixed confidence"
+            );
+            continue;
+        }
+        let coefficients = fit_logistic(&rows);
+        info!(
+            match_type = mt.as_str(),
+            samples = rows.len(),
+            positives,
+            ?coefficients,
+            "fitted"
+        );
+        by_match_type.insert(
+            mt.as_str().to_string(),
+            LogisticCoefficients {
+                coefficients,
+                samples: rows.len(),
+                positives,
+            },
+        );
+    }
+
+    let model = ConfidenceModel {
+        fitted_at: Utc::now().to_rfc3339(),
+        feature_names: FEATURE_NAMES.iter().map(|s| s.to_string()).collect(),
+        density_radius_m: DENSITY_RADIUS_M,
+        by_match_type,
+    };
+    model.save(&args.output)?;
+    info!(artifact=?args.output, "artifact");
+
+    Ok(())
+}
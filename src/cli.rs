@@ -1,3 +1,8 @@
+use crate::locate::PointCrs;
+use crate::prepare::PrepareBackend;
+use anyhow::Result;
+use ban_cadastre::cog::CogHistory;
+use ban_cadastre::structures::{CrossCommunePolicy, MatchConfig, PositionPolicy};
 use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -14,20 +19,37 @@ pub enum Commands {
     /// Match a single scope (one-off / debugging)
     Link(LinkArgs),
     /// Run the full pipeline for multiple departments
+    #[cfg(all(feature = "download", feature = "duckdb-qa"))]
     Pipeline(PipelineArgs),
     /// Analyze results and generate reports
+    #[cfg(feature = "polars-analysis")]
     Analyze(AnalyzeArgs),
     /// Show pipeline status from batch_state.json
+    #[cfg(all(feature = "download", feature = "duckdb-qa"))]
     Status(StatusArgs),
+    /// Score the matcher against a seeded holdout of BAN cad_parcelles links
+    Evaluate(EvaluateArgs),
+    /// Fit the confidence model from BAN cad_parcelles links
+    Calibrate(CalibrateArgs),
+    /// Answer point / address / parcel lookups over HTTP from staged departments
+    Serve(ServeArgs),
+    /// Find the parcel and nearest BAN address of arbitrary points
+    Locate(LocateArgs),
+    /// Match OpenStreetMap address points (PBF extract) and compare them with BAN by parcel
+    Osm(OsmArgs),
+    /// Prepare the downloaded BAN / cadastre files of one department into staging Parquet
+    Prepare(PrepareArgs),
+    /// Prepare any CSV / Parquet point dataset into the address staging schema (column mapping)
+    PreparePoints(PreparePointsArgs),
 }
 
 #[derive(Args, Debug)]
 pub struct LinkArgs {
-    /// Path to prepared addresses Parquet (columns: id, code_insee, geom(WKB EPSG:2154), existing_link)
+    /// Path to prepared addresses Parquet (columns: id, code_insee, geom(WKB, working CRS of the department), existing_link)
     #[arg(long, alias = "addresses")]
     pub input_adresses: PathBuf,
 
-    /// Path to prepared parcels Parquet (columns: id, code_insee, geom(WKB EPSG:2154))
+    /// Path to prepared parcels Parquet (columns: id, code_insee, geom(WKB, working CRS of the department))
     #[arg(long, alias = "parcels")]
     pub input_parcelles: PathBuf,
 
@@ -38,9 +60,18 @@ pub struct LinkArgs {
     #[arg(long, default_value_t = 50.0)]
     pub distance_threshold: f64,
 
+    /// Step 2 candidates kept per address when `--candidates` is set
     #[arg(long, default_value_t = 5)]
     pub num_neighbors: usize,
 
+    /// Candidate mode: write the `num_neighbors` nearest BorderNear parcels per address (column `rank`)
+    #[arg(long, default_value_t = false)]
+    pub candidates: bool,
+
+    /// PreExisting links farther than this (meters) are demoted to PreExistingFar
+    #[arg(long, default_value_t = 100.0)]
+    pub preexisting_max_distance_m: f64,
+
     #[arg(long, default_value_t = 10000)]
     pub batch_size: usize,
 
@@ -49,8 +80,126 @@ pub struct LinkArgs {
 
     #[arg(long)]
     pub filter_commune: Option<String>,
+
+    /// Department of the inputs, selecting the working CRS for the sanity check
+    /// (default: department of the first address code_insee)
+    #[arg(long)]
+    pub dept: Option<String>,
+
+    /// Emit one None row per address without any parcel (nearest parcel as hint)
+    #[arg(long, default_value_t = false)]
+    pub emit_unmatched: bool,
+
+    /// Addresses sharing one exact coordinate with this many addresses or more are tagged as stacked (0 = off)
+    #[arg(long, default_value_t = 10)]
+    pub stacked_min_addresses: usize,
+
+    /// Remove stacked addresses from Inside / BorderNear / FallbackNearest candidates
+    #[arg(long, default_value_t = false)]
+    pub exclude_stacked: bool,
+
+    /// Calibrated confidence model (JSON written by `calibrate`); default: fixed table by match type
+    #[arg(long)]
+    pub confidence_model: Option<PathBuf>,
+
+    /// Prepared buildings Parquet (columns: id, code_insee, geom(WKB, working CRS of the department)); enables building-aware matching
+    #[arg(long, alias = "buildings")]
+    pub input_batiments: Option<PathBuf>,
+
+    /// Buildings: a building within this distance (meters) of the address marks its parcel as preferred
+    #[arg(long, default_value_t = 10.0)]
+    pub building_max_distance_m: f64,
+
+    #[command(flatten)]
+    pub cross_commune: CrossCommuneArgs,
+
+    #[command(flatten)]
+    pub positions: PositionArgs,
+
+    #[command(flatten)]
+    pub cog: CogArgs,
+}
+
+/// Cross-commune policy options shared by `link` and `pipeline`.
+#[derive(Args, Debug)]
+pub struct CrossCommuneArgs {
+    /// Cross-commune policy for Step 1 (Inside)
+    #[arg(long, value_enum, default_value_t = CrossCommunePolicy::Allow)]
+    pub cross_commune_inside: CrossCommunePolicy,
+
+    /// Cross-commune policy for Step 2 (BorderNear)
+    #[arg(long, value_enum, default_value_t = CrossCommunePolicy::Allow)]
+    pub cross_commune_border: CrossCommunePolicy,
+
+    /// Cross-commune policy for Step 3 (FallbackNearest)
+    #[arg(long, value_enum, default_value_t = CrossCommunePolicy::Allow)]
+    pub cross_commune_fallback: CrossCommunePolicy,
+
+    /// Ranking penalty in meters for cross-commune candidates (policy `penalize`)
+    #[arg(long, default_value_t = 50.0)]
+    pub cross_commune_penalty_m: f64,
 }
 
+impl CrossCommuneArgs {
+    pub fn apply(&self, config: &mut MatchConfig) {
+        config.inside_cross_commune = self.cross_commune_inside;
+        config.border_cross_commune = self.cross_commune_border;
+        config.fallback_cross_commune = self.cross_commune_fallback;
+        config.cross_commune_penalty_m = self.cross_commune_penalty_m;
+    }
+}
+
+/// BAN position precision options shared by the matching subcommands.
+#[derive(Args, Debug)]
+pub struct PositionArgs {
+    /// BAN type_position / source_position values treated as low precision (comma-separated)
+    #[arg(long, value_delimiter = ',', default_value = "segment")]
+    pub low_precision_positions: Vec<String>,
+
+    /// Inside / BorderNear policy for low-precision positions
+    #[arg(long, value_enum, default_value_t = PositionPolicy::Allow)]
+    pub low_precision_policy: PositionPolicy,
+}
+
+impl PositionArgs {
+    pub fn apply(&self, config: &mut MatchConfig) {
+        config.low_precision_positions = self.low_precision_positions.clone();
+        config.low_precision_policy = self.low_precision_policy;
+    }
+}
+
+/// Historic commune code (COG) options shared by `link` and `pipeline`.
+#[derive(Args, Debug)]
+pub struct CogArgs {
+    /// INSEE commune movements CSV (`v_mvt_commune_<year>.csv`): address and parcel `code_insee`
+    /// (and `--filter-commune`) are normalized to the reference year
+    #[arg(long)]
+    pub cog_history: Option<PathBuf>,
+
+    /// COG reference year (communes at January 1st); default: all movements of the table
+    #[arg(long, requires = "cog_history")]
+    pub cog_year: Option<i32>,
+
+    /// Fold Paris / Lyon / Marseille arrondissements into their municipal code
+    #[arg(long, default_value_t = false)]
+    pub cog_merge_arrondissements: bool,
+}
+
+impl CogArgs {
+    pub fn load(&self) -> Result<Option<CogHistory>> {
+        match &self.cog_history {
+            Some(path) => Ok(Some(CogHistory::load(
+                path,
+                self.cog_year,
+                self.cog_merge_arrondissements,
+            )?)),
+            None if self.cog_merge_arrondissements => Ok(Some(CogHistory::arrondissements_only())),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(all(feature = "download", feature = "duckdb-qa"))]
 #[derive(Args, Debug)]
 pub struct PipelineArgs {
     /// Departments manifest CSV path (expects a first column containing department code; header allowed)
@@ -82,8 +231,73 @@ pub struct PipelineArgs {
     /// Return exit code 2 if any department failed (partial run)
     #[arg(long, default_value_t = false)]
     pub strict: bool,
+
+    /// Emit one None row per address without any parcel (nearest parcel as hint)
+    #[arg(long, default_value_t = false)]
+    pub emit_unmatched: bool,
+
+    /// Step 2 candidates kept per address when `--candidates` is set
+    #[arg(long, default_value_t = 5)]
+    pub num_neighbors: usize,
+
+    /// Candidate mode: write the `num_neighbors` nearest BorderNear parcels per address (column `rank`)
+    #[arg(long, default_value_t = false)]
+    pub candidates: bool,
+
+    /// PreExisting links farther than this (meters) are demoted to PreExistingFar
+    #[arg(long, default_value_t = 100.0)]
+    pub preexisting_max_distance_m: f64,
+
+    /// Addresses sharing one exact coordinate with this many addresses or more are tagged as stacked (0 = off)
+    #[arg(long, default_value_t = 10)]
+    pub stacked_min_addresses: usize,
+
+    /// Remove stacked addresses from Inside / BorderNear / FallbackNearest candidates
+    #[arg(long, default_value_t = false)]
+    pub exclude_stacked: bool,
+
+    /// Calibrated confidence model (JSON written by `calibrate`); default: fixed table by match type
+    #[arg(long)]
+    pub confidence_model: Option<PathBuf>,
+
+    /// Cross-department halo (meters): border parcels/addresses of staged neighbouring departments
+    /// take part in the matching; only rows owned by the department are written
+    #[arg(long)]
+    pub halo_m: Option<f64>,
+
+    /// Download/prepare the cadastre `batiments` layer and prefer built parcels when matching
+    #[arg(long, default_value_t = false)]
+    pub buildings: bool,
+
+    /// Buildings: a building within this distance (meters) of the address marks its parcel as preferred
+    #[arg(long, default_value_t = 10.0)]
+    pub building_max_distance_m: f64,
+
+    /// Local DuckDB spatial extension file (`spatial.duckdb_extension`) used by prepare;
+    /// default: `INSTALL spatial` (network on first use)
+    #[arg(long)]
+    pub spatial_extension: Option<PathBuf>,
+
+    /// Prepare engine: `duckdb` (spatial extension) or `native` (pure Rust, reads the `.gz` files)
+    #[arg(long, value_enum, default_value_t = PrepareBackend::default())]
+    pub prepare_backend: PrepareBackend,
+
+    /// Local géo-DVF files (`<DEP>.csv.gz` or `<DEP>.csv`): DVF parcels are compared with the
+    /// matched parcels of their BAN address in QA
+    #[arg(long)]
+    pub dvf_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cross_commune: CrossCommuneArgs,
+
+    #[command(flatten)]
+    pub positions: PositionArgs,
+
+    #[command(flatten)]
+    pub cog: CogArgs,
 }
 
+#[cfg(feature = "polars-analysis")]
 #[derive(Args, Debug)]
 pub struct AnalyzeArgs {
     #[arg(long)]
@@ -99,9 +313,254 @@ pub struct AnalyzeArgs {
     pub strict: bool,
 }
 
+#[cfg(all(feature = "download", feature = "duckdb-qa"))]
 #[derive(Args, Debug)]
 pub struct StatusArgs {
     /// Data directory containing batch_state.json
     #[arg(long)]
     pub data_dir: PathBuf,
 }
+
+#[derive(Args, Debug)]
+pub struct EvaluateArgs {
+    /// Path to prepared addresses Parquet (columns: id, code_insee, geom(WKB, working CRS of the department), existing_link)
+    #[arg(long, alias = "addresses")]
+    pub input_adresses: PathBuf,
+
+    /// Path to prepared parcels Parquet (columns: id, code_insee, geom(WKB, working CRS of the department))
+    #[arg(long, alias = "parcels")]
+    pub input_parcelles: PathBuf,
+
+    /// Directory for evaluation_summary.json and evaluation_by_*.csv
+    #[arg(long)]
+    pub output_dir: PathBuf,
+
+    /// Fraction of addresses with existing_link whose link is hidden and used as ground truth
+    #[arg(long, default_value_t = 0.2)]
+    pub holdout_fraction: f64,
+
+    /// Seed of the holdout draw (same seed = same hidden addresses)
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+
+    #[arg(long, default_value_t = 50.0)]
+    pub distance_threshold: f64,
+
+    #[command(flatten)]
+    pub cross_commune: CrossCommuneArgs,
+
+    #[command(flatten)]
+    pub positions: PositionArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct CalibrateArgs {
+    /// Path to prepared addresses Parquet (columns: id, code_insee, geom(WKB, working CRS of the department), existing_link)
+    #[arg(long, alias = "addresses")]
+    pub input_adresses: PathBuf,
+
+    /// Path to prepared parcels Parquet (columns: id, code_insee, geom(WKB, working CRS of the department))
+    #[arg(long, alias = "parcels")]
+    pub input_parcelles: PathBuf,
+
+    /// Output path for the confidence model JSON
+    #[arg(long)]
+    pub output: PathBuf,
+
+    #[arg(long, default_value_t = 50.0)]
+    pub distance_threshold: f64,
+
+    #[command(flatten)]
+    pub cross_commune: CrossCommuneArgs,
+
+    #[command(flatten)]
+    pub positions: PositionArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Directory of `parcelles_<DEP>.parquet` / `adresses_<DEP>.parquet` (pipeline `staging/`)
+    #[arg(long)]
+    pub staging_dir: PathBuf,
+
+    /// Departments to load (comma-separated)
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub departments: Vec<String>,
+
+    /// Listen address
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub bind: String,
+
+    /// Threads answering requests
+    #[arg(long, default_value_t = 4)]
+    pub workers: usize,
+
+    #[arg(long, default_value_t = 50.0)]
+    pub distance_threshold: f64,
+
+    /// PreExisting links farther than this (meters) are demoted to PreExistingFar
+    #[arg(long, default_value_t = 100.0)]
+    pub preexisting_max_distance_m: f64,
+
+    /// Calibrated confidence model (JSON written by `calibrate`); default: fixed table by match type
+    #[arg(long)]
+    pub confidence_model: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cross_commune: CrossCommuneArgs,
+
+    #[command(flatten)]
+    pub positions: PositionArgs,
+
+    #[command(flatten)]
+    pub cog: CogArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct LocateArgs {
+    /// Points to locate: CSV (`,` or `;` separated) or Parquet, one point per row
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// CRS of the input coordinates
+    #[arg(long, value_enum, default_value_t = PointCrs::Wgs84)]
+    pub input_crs: PointCrs,
+
+    /// Point id column
+    #[arg(long, default_value = "id")]
+    pub id_column: String,
+
+    /// Longitude / x column (default: `lon` for wgs84, `x` for lambert93)
+    #[arg(long)]
+    pub x_column: Option<String>,
+
+    /// Latitude / y column (default: `lat` for wgs84, `y` for lambert93)
+    #[arg(long)]
+    pub y_column: Option<String>,
+
+    /// Path to prepared parcels Parquet (columns: id, code_insee, geom(WKB, working CRS of the department))
+    #[arg(long, alias = "parcels")]
+    pub input_parcelles: PathBuf,
+
+    /// Path to prepared addresses Parquet (columns: id, code_insee, geom(WKB, working CRS of the department), existing_link)
+    #[arg(long, alias = "addresses")]
+    pub input_adresses: PathBuf,
+
+    /// Department of the inputs, selecting the working CRS (default: department of the first parcel code_insee)
+    #[arg(long)]
+    pub dept: Option<String>,
+
+    /// Parcels and addresses farther than this (meters) are left empty (default: no limit)
+    #[arg(long)]
+    pub max_distance_m: Option<f64>,
+
+    /// Output path: CSV if it ends in `.csv`, Parquet otherwise
+    #[arg(long)]
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct PrepareArgs {
+    /// Department to prepare
+    #[arg(long)]
+    pub dept: String,
+
+    /// Pipeline data directory: reads `raw/` (`.gz` downloads for `native`, decompressed files
+    /// for `duckdb`), writes `staging/`
+    #[arg(long)]
+    pub data_dir: PathBuf,
+
+    /// Also prepare the cadastre `batiments` layer
+    #[arg(long, default_value_t = false)]
+    pub buildings: bool,
+
+    /// Prepare again when the staging file exists
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+
+    /// Local DuckDB spatial extension file (`spatial.duckdb_extension`) used by prepare;
+    /// default: `INSTALL spatial` (network on first use)
+    #[arg(long)]
+    pub spatial_extension: Option<PathBuf>,
+
+    /// Prepare engine: `duckdb` (spatial extension, feature `duckdb-qa`) or `native` (pure Rust);
+    /// default: `duckdb` when built with `duckdb-qa`
+    #[arg(long, value_enum, default_value_t = PrepareBackend::default())]
+    pub prepare_backend: PrepareBackend,
+}
+
+#[derive(Args, Debug)]
+pub struct PreparePointsArgs {
+    /// Point dataset: CSV (`,` or `;` separated, optionally `.gz`) or Parquet
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Staging Parquet to write (address schema, input of `link --addresses`)
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// Department of the points, selecting the working CRS
+    #[arg(long)]
+    pub dept: String,
+
+    /// JSON column mapping (keys: id, code_insee, x, y, lon, lat, existing_link, type_position,
+    /// source_position); missing keys keep the BAN names, `null` marks an absent column
+    #[arg(long)]
+    pub columns: Option<PathBuf>,
+
+    /// Overwrite an existing output
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+
+    /// Local DuckDB spatial extension file (`spatial.duckdb_extension`) used by prepare;
+    /// default: `INSTALL spatial` (network on first use)
+    #[arg(long)]
+    pub spatial_extension: Option<PathBuf>,
+
+    /// Prepare engine: `duckdb` (spatial extension, feature `duckdb-qa`) or `native` (pure Rust);
+    /// default: `duckdb` when built with `duckdb-qa`
+    #[arg(long, value_enum, default_value_t = PrepareBackend::default())]
+    pub prepare_backend: PrepareBackend,
+}
+
+#[derive(Args, Debug)]
+pub struct OsmArgs {
+    /// OpenStreetMap extract of the department (`.osm.pbf`)
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Path to prepared parcels Parquet (columns: id, code_insee, geom(WKB, working CRS of the department))
+    #[arg(long, alias = "parcels")]
+    pub input_parcelles: PathBuf,
+
+    /// Output path for the OSM matches Parquet (`id_ban` = `node/<id>` or `way/<id>`)
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// Department of the inputs, selecting the working CRS (default: department of the first parcel code_insee)
+    #[arg(long)]
+    pub dept: Option<String>,
+
+    /// Prepared BAN addresses Parquet, matched with the same settings for `--conflation`
+    #[arg(long, alias = "addresses", requires = "conflation")]
+    pub input_adresses: Option<PathBuf>,
+
+    /// Per-parcel BAN / OSM address counts CSV (status both / ban_only / osm_only)
+    #[arg(long, requires = "input_adresses")]
+    pub conflation: Option<PathBuf>,
+
+    /// Step 2 (BorderNear) radius in meters; OSM points farther than this from every parcel are
+    /// dropped as outside the department
+    #[arg(long, default_value_t = 50.0)]
+    pub distance_threshold: f64,
+
+    /// Calibrated confidence model (JSON written by `calibrate`); default: fixed table by match type
+    #[arg(long)]
+    pub confidence_model: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub cross_commune: CrossCommuneArgs,
+
+    #[command(flatten)]
+    pub positions: PositionArgs,
+}
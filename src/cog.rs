@@ -0,0 +1,207 @@
+use crate::parcel_id::ARRONDISSEMENTS;
+use anyhow::{anyhow, Context, Result};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Successor chains longer than this are treated as a loop in the table.
+const MAX_HOPS: usize = 64;
+
+/// Commune codes of the COG (Code officiel géographique) at a reference year, built from the
+/// INSEE commune movements table (`v_mvt_commune_<year>.csv`: mod, date_eff, typecom_av,
+/// com_av, ..., typecom_ap, com_ap, ...).
+pub struct CogHistory {
+    /// Merged / renumbered commune → commune it became (one hop; follow until absent).
+    successor: HashMap<String, String>,
+    /// Paris / Lyon / Marseille arrondissements are folded into the municipal code.
+    merge_arrondissements: bool,
+}
+
+impl CogHistory {
+    /// Applies the movements effective on or before January 1st of `year` (all movements if None).
+    /// A commune split between several communes (rétablissement) keeps its code.
+    pub fn load(path: &Path, year: Option<i32>, merge_arrondissements: bool) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed reading COG history CSV: {:?}", path))?;
+        // Column names are upper-case in recent INSEE releases, lower-case before.
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| -> Result<usize> {
+            headers
+                .iter()
+                .position(|c| c.trim().eq_ignore_ascii_case(name))
+                .ok_or_else(|| anyhow!("COG history: missing column {}", name))
+        };
+        let date_eff = column("date_eff")?;
+        let typecom_av = column("typecom_av")?;
+        let com_av = column("com_av")?;
+        let typecom_ap = column("typecom_ap")?;
+        let com_ap = column("com_ap")?;
+
+        let cutoff = year.map(|y| format!("{:04}-01-01", y));
+        // date → commune before → communes after (COM rows only)
+        let mut events: BTreeMap<String, BTreeMap<String, HashSet<String>>> = BTreeMap::new();
+        for record in reader.records() {
+            let record =
+                record.with_context(|| format!("Failed reading COG history CSV: {:?}", path))?;
+            let (Some(date), Some(tav), Some(av), Some(tap), Some(ap)) = (
+                record.get(date_eff).map(str::trim),
+                record.get(typecom_av).map(str::trim),
+                record.get(com_av).map(str::trim),
+                record.get(typecom_ap).map(str::trim),
+                record.get(com_ap).map(str::trim),
+            ) else {
+                continue;
+            };
+            if tav != "COM" || tap != "COM" || av.is_empty() || ap.is_empty() {
+                continue;
+            }
+            if cutoff.as_deref().is_some_and(|c| date > c) {
+                continue;
+            }
+            events
+                .entry(date.to_string())
+                .or_default()
+                .entry(av.to_string())
+                .or_default()
+                .insert(ap.to_string());
+        }
+
+        let mut successor: HashMap<String, String> = HashMap::new();
+        let mut ambiguous = 0usize;
+        for communes in events.into_values() {
+            let mut moved = HashSet::new();
+            for (av, aps) in &communes {
+                if aps.contains(av) {
+                    successor.remove(av);
+                } else if aps.len() == 1 {
+                    let ap = aps.iter().next().expect("one successor");
+                    successor.insert(av.clone(), ap.clone());
+                    moved.insert(av.as_str());
+                } else {
+                    ambiguous += 1;
+                }
+            }
+            // A commune existing after the movement (created, restored) is its own code again.
+            for ap in communes.values().flatten() {
+                if !moved.contains(ap.as_str()) {
+                    successor.remove(ap);
+                }
+            }
+        }
+        if ambiguous > 0 {
+            warn!(
+                ambiguous,
+                "COG history: communes split without keeping their code; left as is"
+            );
+        }
+        info!(path=?path, year=?year, mapped_communes=successor.len(), "COG history loaded");
+
+        Ok(Self {
+            successor,
+            merge_arrondissements,
+        })
+    }
+
+    /// Arrondissement folding only (no movements table).
+    pub fn arrondissements_only() -> Self {
+        Self {
+            successor: HashMap::new(),
+            merge_arrondissements: true,
+        }
+    }
+
+    /// Commune code at the reference year; unknown codes are returned unchanged.
+    pub fn normalize<'a>(&self, code: &'a str) -> Cow<'a, str> {
+        let mut current = Cow::Borrowed(code.trim());
+        for _ in 0..MAX_HOPS {
+            match self.successor.get(current.as_ref()) {
+                Some(next) if next.as_str() != current.as_ref() => {
+                    current = Cow::Owned(next.clone())
+                }
+                _ => break,
+            }
+        }
+        if self.merge_arrondissements {
+            if let Ok(n) = current.parse::<u32>() {
+                if let Some((municipal, _, _)) = ARRONDISSEMENTS
+                    .iter()
+                    .find(|(_, first, last)| (*first..=*last).contains(&n))
+                {
+                    return Cow::Borrowed(municipal);
+                }
+            }
+        }
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const HEADER: &str = "MOD,DATE_EFF,TYPECOM_AV,COM_AV,LIBELLE_AV,TYPECOM_AP,COM_AP,LIBELLE_AP";
+
+    fn history(name: &str, rows: &[&str], year: Option<i32>, arrondissements: bool) -> CogHistory {
+        let path = std::env::temp_dir().join(format!(
+            "ban_cadastre_cog_{}_{}.csv",
+            name,
+            std::process::id()
+        ));
+        let mut f = std::fs::File::create(&path).unwrap();
+        writeln!(f, "{}", HEADER).unwrap();
+        for r in rows {
+            writeln!(f, "{}", r).unwrap();
+        }
+        drop(f);
+        let cog = CogHistory::load(&path, year, arrondissements).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        cog
+    }
+
+    // Commune nouvelle 01453 from 01453 and 01106 (01106 becomes a commune déléguée).
+    const MERGE_2019: [&str; 4] = [
+        "32,2019-01-01,COM,01453,A,COM,01453,A",
+        "32,2019-01-01,COM,01453,A,COMD,01453,A",
+        "32,2019-01-01,COM,01106,B,COM,01453,A",
+        "32,2019-01-01,COM,01106,B,COMD,01106,B",
+    ];
+
+    #[test]
+    fn merged_commune_takes_successor_code() {
+        let cog = history("merge", &MERGE_2019, None, false);
+        assert_eq!(cog.normalize("01106"), "01453");
+        assert_eq!(cog.normalize("01453"), "01453");
+        assert_eq!(cog.normalize("02000"), "02000");
+    }
+
+    #[test]
+    fn restored_commune_gets_its_code_back() {
+        let mut rows = MERGE_2019.to_vec();
+        rows.push("21,2022-01-01,COM,01453,A,COM,01453,A");
+        rows.push("21,2022-01-01,COM,01453,A,COM,01106,B");
+        let cog = history("restore", &rows, None, false);
+        assert_eq!(cog.normalize("01106"), "01106");
+        assert_eq!(cog.normalize("01453"), "01453");
+    }
+
+    #[test]
+    fn reference_year_ignores_later_movements() {
+        let cog = history("cutoff", &MERGE_2019, Some(2018), false);
+        assert_eq!(cog.normalize("01106"), "01106");
+        let cog = history("cutoff_in", &MERGE_2019, Some(2019), false);
+        assert_eq!(cog.normalize("01106"), "01453");
+    }
+
+    #[test]
+    fn arrondissements_fold_into_municipal_code() {
+        let cog = CogHistory::arrondissements_only();
+        assert_eq!(cog.normalize("75101"), "75056");
+        assert_eq!(cog.normalize("69389"), "69123");
+        assert_eq!(cog.normalize("13216"), "13055");
+        assert_eq!(cog.normalize("75121"), "75121");
+        let cog = history("no_arm", &[], None, false);
+        assert_eq!(cog.normalize("75101"), "75101");
+    }
+}
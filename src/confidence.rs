@@ -0,0 +1,199 @@
+use crate::structures::{MatchType, AMBIGUITY_MARGIN_CAP_M};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Radius (meters) used to count neighbouring addresses (local density feature).
+pub const DENSITY_RADIUS_M: f64 = 50.0;
+
+pub const FEATURE_NAMES: [&str; 7] = [
+    "intercept",
+    "ln1p_distance_m",
+    "margin_ratio",
+    "has_runner_up",
+    "ln1p_address_density",
+    "ln1p_parcel_area_m2",
+    "cross_commune",
+];
+
+/// Inputs of a confidence scorer for one emitted link.
+#[derive(Debug, Clone)]
+pub struct ConfidenceFeatures {
+    pub match_type: MatchType,
+    pub distance_m: f32,
+    pub margin_m: Option<f32>,
+    /// Other addresses within `DENSITY_RADIUS_M` of the address.
+    pub address_density: usize,
+    pub parcel_area_m2: f64,
+    pub cross_commune: bool,
+}
+
+impl ConfidenceFeatures {
+    pub fn to_vector(&self) -> [f64; FEATURE_NAMES.len()] {
+        let (margin_ratio, has_runner_up) = match self.margin_m {
+            Some(m) => ((m as f64 / AMBIGUITY_MARGIN_CAP_M).clamp(0.0, 1.0), 1.0),
+            None => (1.0, 0.0),
+        };
+        [
+            1.0,
+            (self.distance_m.max(0.0) as f64).ln_1p(),
+            margin_ratio,
+            has_runner_up,
+            (self.address_density as f64).ln_1p(),
+            self.parcel_area_m2.max(0.0).ln_1p(),
+            if self.cross_commune { 1.0 } else { 0.0 },
+        ]
+    }
+}
+
+/// Pluggable confidence scorer. Returns a probability in [0, 1] that the link is correct,
+/// or None to keep the deterministic confidence set by `MatchOutput::new`.
+pub trait ConfidenceScorer: Send + Sync + fmt::Debug {
+    fn probability(&self, features: &ConfidenceFeatures) -> Option<f64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogisticCoefficients {
+    pub coefficients: Vec<f64>,
+    pub samples: usize,
+    pub positives: usize,
+}
+
+/// Per-match-type logistic regression fitted on PreExisting ground truth (`calibrate`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfidenceModel {
+    pub fitted_at: String,
+    pub feature_names: Vec<String>,
+    pub density_radius_m: f64,
+    pub by_match_type: HashMap<String, LogisticCoefficients>,
+}
+
+impl ConfidenceModel {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open confidence model: {:?}", path))?;
+        let model: Self = serde_json::from_reader(BufReader::new(file))
+            .context("Failed to parse confidence model")?;
+        if model.feature_names != FEATURE_NAMES {
+            return Err(anyhow::anyhow!(
+                "Confidence model features {:?} do not match expected {:?}",
+                model.feature_names,
+                FEATURE_NAMES
+            ));
+        }
+        Ok(model)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).context("Failed to create confidence model file")?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .context("Failed to write confidence model")?;
+        Ok(())
+    }
+}
+
+impl ConfidenceScorer for ConfidenceModel {
+    fn probability(&self, features: &ConfidenceFeatures) -> Option<f64> {
+        let c = self.by_match_type.get(features.match_type.as_str())?;
+        let x = features.to_vector();
+        let z: f64 = c
+            .coefficients
+            .iter()
+            .zip(x.iter())
+            .map(|(w, v)| w * v)
+            .sum();
+        Some(sigmoid(z))
+    }
+}
+
+/// Loads the `--confidence-model` file, if any.
+pub fn load_scorer(path: Option<&Path>) -> Result<Option<Arc<dyn ConfidenceScorer>>> {
+    match path {
+        Some(p) => Ok(Some(Arc::new(ConfidenceModel::load(p)?))),
+        None => Ok(None),
+    }
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+/// Ridge-regularised logistic regression (Newton / IRLS). The intercept is not penalised.
+pub fn fit_logistic(samples: &[([f64; FEATURE_NAMES.len()], bool)]) -> Vec<f64> {
+    const N: usize = FEATURE_NAMES.len();
+    const RIDGE: f64 = 1e-3;
+    const MAX_ITER: usize = 50;
+
+    let mut w = [0.0_f64; N];
+    if samples.is_empty() {
+        return w.to_vec();
+    }
+
+    for _ in 0..MAX_ITER {
+        let mut grad = [0.0_f64; N];
+        let mut hess = [[0.0_f64; N]; N];
+        for (x, y) in samples {
+            let z: f64 = w.iter().zip(x.iter()).map(|(a, b)| a * b).sum();
+            let p = sigmoid(z);
+            let r = if *y { 1.0 } else { 0.0 } - p;
+            let s = (p * (1.0 - p)).max(1e-9);
+            for i in 0..N {
+                grad[i] += r * x[i];
+                for j in 0..N {
+                    hess[i][j] += s * x[i] * x[j];
+                }
+            }
+        }
+        for i in 1..N {
+            grad[i] -= RIDGE * samples.len() as f64 * w[i];
+            hess[i][i] += RIDGE * samples.len() as f64;
+        }
+        // Features constant across samples leave a singular system: keep a tiny diagonal.
+        hess[0][0] += 1e-9;
+
+        let Some(step) = solve(hess, grad) else {
+            break;
+        };
+        let mut max_step = 0.0_f64;
+        for i in 0..N {
+            w[i] += step[i];
+            max_step = max_step.max(step[i].abs());
+        }
+        if max_step < 1e-8 {
+            break;
+        }
+    }
+
+    w.to_vec()
+}
+
+/// Gaussian elimination with partial pivoting.
+fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in (col + 1)..N {
+            let pivot_row = a[col];
+            let f = a[row][col] / pivot_row[col];
+            for (v, p) in a[row].iter_mut().zip(pivot_row.iter()).skip(col) {
+                *v -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = [0.0_f64; N];
+    for row in (0..N).rev() {
+        let s: f64 = ((row + 1)..N).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - s) / a[row][row];
+    }
+    Some(x)
+}
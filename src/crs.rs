@@ -0,0 +1,236 @@
+/// Projected CRS in which a department is prepared and matched (meters).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkingCrs {
+    /// DuckDB spatial / PROJ identifier, e.g. `EPSG:2154`.
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Plausible extent of the territory in this CRS: (min_x, min_y, max_x, max_y).
+    pub extent: (f64, f64, f64, f64),
+    /// Map projection from CRS84 longitude/latitude (GRS80 ellipsoid, no datum shift: the French
+    /// geodetic systems are ITRS realisations, within a few centimetres of WGS84).
+    pub projection: Projection,
+}
+
+/// Projection parameters (angles in degrees).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Lambert conformal conic, two standard parallels (EPSG method 9802).
+    LambertConic {
+        lat_1: f64,
+        lat_2: f64,
+        lat_0: f64,
+        lon_0: f64,
+        x_0: f64,
+        y_0: f64,
+    },
+    /// UTM zone (transverse Mercator, k0 = 0.9996).
+    Utm { zone: u8, south: bool },
+}
+
+const GRS80_A: f64 = 6_378_137.0;
+const GRS80_F: f64 = 1.0 / 298.257_222_101;
+
+/// RGF93 / Lambert-93, metropolitan France and Corsica.
+pub const LAMBERT_93: WorkingCrs = WorkingCrs {
+    code: "EPSG:2154",
+    name: "RGF93 / Lambert-93",
+    extent: (50_000.0, 6_000_000.0, 1_300_000.0, 7_200_000.0),
+    projection: Projection::LambertConic {
+        lat_1: 49.0,
+        lat_2: 44.0,
+        lat_0: 46.5,
+        lon_0: 3.0,
+        x_0: 700_000.0,
+        y_0: 6_600_000.0,
+    },
+};
+
+/// Overseas departments: (department, CRS).
+const OVERSEAS: [(&str, WorkingCrs); 5] = [
+    (
+        "971",
+        WorkingCrs {
+            code: "EPSG:5490",
+            name: "RGAF09 / UTM zone 20N",
+            extent: (550_000.0, 1_500_000.0, 800_000.0, 1_900_000.0),
+            projection: Projection::Utm {
+                zone: 20,
+                south: false,
+            },
+        },
+    ),
+    (
+        "972",
+        WorkingCrs {
+            code: "EPSG:5490",
+            name: "RGAF09 / UTM zone 20N",
+            extent: (550_000.0, 1_500_000.0, 800_000.0, 1_900_000.0),
+            projection: Projection::Utm {
+                zone: 20,
+                south: false,
+            },
+        },
+    ),
+    (
+        "973",
+        WorkingCrs {
+            code: "EPSG:2972",
+            name: "RGFG95 / UTM zone 22N",
+            extent: (50_000.0, 200_000.0, 500_000.0, 700_000.0),
+            projection: Projection::Utm {
+                zone: 22,
+                south: false,
+            },
+        },
+    ),
+    (
+        "974",
+        WorkingCrs {
+            code: "EPSG:2975",
+            name: "RGR92 / UTM zone 40S",
+            extent: (300_000.0, 7_600_000.0, 400_000.0, 7_720_000.0),
+            projection: Projection::Utm {
+                zone: 40,
+                south: true,
+            },
+        },
+    ),
+    (
+        "976",
+        WorkingCrs {
+            code: "EPSG:4471",
+            name: "RGM04 / UTM zone 38S",
+            extent: (480_000.0, 8_540_000.0, 550_000.0, 8_630_000.0),
+            projection: Projection::Utm {
+                zone: 38,
+                south: true,
+            },
+        },
+    ),
+];
+
+/// Working CRS of a department code (`01`..`95`, `2A`, `2B`, `971`..`976`).
+pub fn department_crs(dept: &str) -> WorkingCrs {
+    OVERSEAS
+        .iter()
+        .find(|(d, _)| *d == dept.trim())
+        .map(|(_, crs)| *crs)
+        .unwrap_or(LAMBERT_93)
+}
+
+/// Department of a commune INSEE code (`97xxx` → 3 characters).
+pub fn commune_department(code_insee: &str) -> Option<&str> {
+    let code = code_insee.trim();
+    let len = if code.starts_with("97") { 3 } else { 2 };
+    code.get(..len)
+}
+
+impl WorkingCrs {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.extent;
+        (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+    }
+
+    /// CRS84 (longitude, latitude in degrees) → (x, y) in meters.
+    pub fn project(&self, lon: f64, lat: f64) -> (f64, f64) {
+        match self.projection {
+            Projection::LambertConic {
+                lat_1,
+                lat_2,
+                lat_0,
+                lon_0,
+                x_0,
+                y_0,
+            } => lambert_conic(lon, lat, (lat_1, lat_2, lat_0, lon_0), (x_0, y_0)),
+            Projection::Utm { zone, south } => utm(lon, lat, zone, south),
+        }
+    }
+}
+
+/// EPSG Guidance Note 7-2, §3.2.1.1.
+fn lambert_conic(
+    lon: f64,
+    lat: f64,
+    (lat_1, lat_2, lat_0, lon_0): (f64, f64, f64, f64),
+    (x_0, y_0): (f64, f64),
+) -> (f64, f64) {
+    let e = (GRS80_F * (2.0 - GRS80_F)).sqrt();
+    let m = |phi: f64| phi.cos() / (1.0 - (e * phi.sin()).powi(2)).sqrt();
+    let t = |phi: f64| {
+        let es = e * phi.sin();
+        (std::f64::consts::FRAC_PI_4 - phi / 2.0).tan() / ((1.0 - es) / (1.0 + es)).powf(e / 2.0)
+    };
+    let (phi_1, phi_2) = (lat_1.to_radians(), lat_2.to_radians());
+    let n = (m(phi_1).ln() - m(phi_2).ln()) / (t(phi_1).ln() - t(phi_2).ln());
+    let big_f = m(phi_1) / (n * t(phi_1).powf(n));
+    let r = |phi: f64| GRS80_A * big_f * t(phi).powf(n);
+    let r_0 = r(lat_0.to_radians());
+    let r_phi = r(lat.to_radians());
+    let theta = n * (lon - lon_0).to_radians();
+    (x_0 + r_phi * theta.sin(), y_0 + r_0 - r_phi * theta.cos())
+}
+
+/// Krüger series to n³ (sub-millimetre inside a zone).
+fn utm(lon: f64, lat: f64, zone: u8, south: bool) -> (f64, f64) {
+    const K_0: f64 = 0.9996;
+    let n = GRS80_F / (2.0 - GRS80_F);
+    let big_a = GRS80_A / (1.0 + n) * (1.0 + n * n / 4.0 + n.powi(4) / 64.0);
+    let alpha = [
+        n / 2.0 - 2.0 * n * n / 3.0 + 5.0 * n.powi(3) / 16.0,
+        13.0 * n * n / 48.0 - 3.0 * n.powi(3) / 5.0,
+        61.0 * n.powi(3) / 240.0,
+    ];
+    let lon_0 = f64::from(zone) * 6.0 - 183.0;
+    let (phi, dlambda) = (lat.to_radians(), (lon - lon_0).to_radians());
+    let c = 2.0 * n.sqrt() / (1.0 + n);
+    let t = (phi.sin().atanh() - c * (c * phi.sin()).atanh()).sinh();
+    let xi = t.atan2(dlambda.cos());
+    let eta = (dlambda.sin() / (1.0 + t * t).sqrt()).atanh();
+    let (mut easting, mut northing) = (eta, xi);
+    for (j, a) in alpha.iter().enumerate() {
+        let k = 2.0 * (j + 1) as f64;
+        easting += a * (k * xi).cos() * (k * eta).sinh();
+        northing += a * (k * xi).sin() * (k * eta).cosh();
+    }
+    let false_northing = if south { 10_000_000.0 } else { 0.0 };
+    (
+        500_000.0 + K_0 * big_a * easting,
+        false_northing + K_0 * big_a * northing,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::LAMBERT_93_ORIGIN;
+
+    #[test]
+    fn overseas_departments_use_utm() {
+        assert_eq!(department_crs("69").code, "EPSG:2154");
+        assert_eq!(department_crs("2A").code, "EPSG:2154");
+        assert_eq!(department_crs("972").code, "EPSG:5490");
+        assert_eq!(department_crs("974").code, "EPSG:2975");
+        assert_eq!(commune_department("97411"), Some("974"));
+        assert_eq!(commune_department("2A004"), Some("2A"));
+    }
+
+    #[test]
+    fn projections_hit_reference_points() {
+        let close = |(x, y): (f64, f64), (ex, ey): (f64, f64)| {
+            assert!((x - ex).abs() < 0.01 && (y - ey).abs() < 0.01, "{x} {y}")
+        };
+        // Lambert-93 natural origin, and symmetry about the central meridian.
+        close(LAMBERT_93.project(3.0, 46.5), LAMBERT_93_ORIGIN);
+        let (east, y_east) = LAMBERT_93.project(5.0, 45.0);
+        close(LAMBERT_93.project(1.0, 45.0), (1_400_000.0 - east, y_east));
+        // UTM: central meridian on the equator, then 0.9996 × GRS80 meridian arc at 45°.
+        close(
+            department_crs("974").project(57.0, 0.0),
+            (500_000.0, 10_000_000.0),
+        );
+        close(
+            department_crs("972").project(-63.0, 45.0),
+            (500_000.0, 4_982_950.40),
+        );
+    }
+}
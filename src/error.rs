@@ -0,0 +1,28 @@
+use std::fmt;
+
+/// Errors of the library API: `MatchConfigBuilder::build`, the `ParcelData` / `AddressInput`
+/// constructors and `Matcher`. File readers and writers (`loader`, `writer`, `cog`) return
+/// `anyhow::Result` with the offending path in the context.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A `MatchConfig` value out of its domain (negative or non-finite distance, zero count).
+    InvalidConfig { field: &'static str, reason: String },
+    /// A parcel geometry without extent (no polygon, or only empty rings).
+    EmptyGeometry { id: String },
+    /// A coordinate that is NaN or infinite.
+    NonFiniteCoordinate { id: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidConfig { field, reason } => {
+                write!(f, "invalid match config: {} {}", field, reason)
+            }
+            Error::EmptyGeometry { id } => write!(f, "parcel {}: empty geometry", id),
+            Error::NonFiniteCoordinate { id } => write!(f, "{}: non-finite coordinate", id),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
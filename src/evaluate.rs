@@ -0,0 +1,284 @@
+use crate::cli::EvaluateArgs;
+use anyhow::{anyhow, Result};
+use ban_cadastre::loader::{load_addresses, load_parcels};
+use ban_cadastre::matcher::{match_parcels_and_addresses_3_steps, split_links};
+use ban_cadastre::parcel_id::ParcelIdResolver;
+use ban_cadastre::structures::{MatchConfig, MatchOutput, MatchType};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Geometric match types scored against the hidden links.
+const SCORED_TYPES: [MatchType; 3] = [
+    MatchType::Inside,
+    MatchType::BorderNear,
+    MatchType::FallbackNearest,
+];
+
+/// Distance bands (upper bound inclusive, meters) for the best geometric match.
+const DISTANCE_BANDS: [(&str, f32); 6] = [
+    ("0-5", 5.0),
+    ("5-15", 15.0),
+    ("15-50", 50.0),
+    ("50-250", 250.0),
+    ("250-1500", 1500.0),
+    (">1500", f32::INFINITY),
+];
+
+#[derive(Serialize)]
+struct EvaluationSummary {
+    generated_at: String,
+    seed: u64,
+    holdout_fraction: f64,
+    address_max_distance_m: f64,
+    fallback_max_distance_m: f64,
+
+    linked_addresses: usize,
+    holdout_addresses: usize,
+    /// Held-out addresses whose link points to no known parcel (not scored).
+    holdout_unresolved: usize,
+    scored_addresses: usize,
+
+    predicted_addresses: usize,
+    correct_addresses: usize,
+    precision_pct: f64,
+    recall_pct: f64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Tally {
+    predicted: usize,
+    correct: usize,
+}
+
+/// Deterministic draw in [0, 1) from (seed, address id): FNV-1a + splitmix64 finalizer.
+/// Independent of the input row order, so the same seed always hides the same addresses.
+fn holdout_draw(seed: u64, id: &str) -> f64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in id.as_bytes() {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    let mut z = h ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn pct(num: usize, den: usize) -> f64 {
+    if den > 0 {
+        num as f64 / den as f64 * 100.0
+    } else {
+        0.0
+    }
+}
+
+pub fn run_evaluate(args: EvaluateArgs) -> Result<()> {
+    if !(0.0..=1.0).contains(&args.holdout_fraction) {
+        return Err(anyhow!(
+            "holdout fraction must be within [0, 1], got {}",
+            args.holdout_fraction
+        ));
+    }
+    info!(
+        input_addresses=?args.input_adresses,
+        input_parcels=?args.input_parcelles,
+        output_dir=?args.output_dir,
+        seed=args.seed,
+        holdout_fraction=args.holdout_fraction,
+        "starting evaluate mode"
+    );
+    std::fs::create_dir_all(&args.output_dir)?;
+
+    let start_load = Instant::now();
+    let parcels = load_parcels(&args.input_parcelles)?;
+    let mut addresses = load_addresses(&args.input_adresses)?;
+    info!(
+        parcels = parcels.len(),
+        addresses = addresses.len(),
+        elapsed_ms = start_load.elapsed().as_millis(),
+        "loaded inputs"
+    );
+
+    // Hide a seeded fraction of existing_link values (ground truth).
+    let resolver = ParcelIdResolver::new(parcels.iter().map(|p| p.id.as_str()));
+    let mut hidden: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut linked_addresses = 0usize;
+    let mut holdout_addresses = 0usize;
+    let mut holdout_unresolved = 0usize;
+    for addr in addresses.iter_mut() {
+        let Some(links) = &addr.existing_link else {
+            continue;
+        };
+        linked_addresses += 1;
+        if holdout_draw(args.seed, &addr.id) >= args.holdout_fraction {
+            continue;
+        }
+        holdout_addresses += 1;
+        let truth: HashSet<String> = split_links(links)
+            .filter_map(|link| resolver.resolve(link))
+            .map(|r| r.id().to_owned())
+            .collect();
+        addr.existing_link = None;
+        if truth.is_empty() {
+            holdout_unresolved += 1;
+        } else {
+            hidden.insert(addr.id.clone(), truth);
+        }
+    }
+    info!(
+        linked_addresses,
+        holdout_addresses,
+        holdout_unresolved,
+        scored_addresses = hidden.len(),
+        "holdout drawn"
+    );
+    if hidden.is_empty() {
+        warn!("no resolvable held-out link; evaluation metrics will be empty");
+    }
+
+    let mut config = MatchConfig {
+        address_max_distance_m: args.distance_threshold,
+        ..MatchConfig::default()
+    };
+    args.cross_commune.apply(&mut config);
+    args.positions.apply(&mut config);
+
+    info!(?config, "running matcher");
+    let start_match = Instant::now();
+    let matches = match_parcels_and_addresses_3_steps(&parcels, &addresses, None, &config);
+    info!(
+        elapsed_ms = start_match.elapsed().as_millis(),
+        matches = matches.len(),
+        "matching completed"
+    );
+
+    // Best geometric match per held-out address (same ranking as QA best-per-address).
+    let mut best: HashMap<&str, &MatchOutput> = HashMap::new();
+    for m in &matches {
+        if m.rank != 1 || m.id_parcelle.is_none() || !SCORED_TYPES.contains(&m.match_type) {
+            continue;
+        }
+        if !hidden.contains_key(&m.id_ban) {
+            continue;
+        }
+        let better = match best.get(m.id_ban.as_str()) {
+            None => true,
+            Some(b) => {
+                (m.match_type.priority(), m.distance_m, &m.id_parcelle).partial_cmp(&(
+                    b.match_type.priority(),
+                    b.distance_m,
+                    &b.id_parcelle,
+                )) == Some(std::cmp::Ordering::Less)
+            }
+        };
+        if better {
+            best.insert(m.id_ban.as_str(), m);
+        }
+    }
+
+    let mut by_type: HashMap<&'static str, Tally> = HashMap::new();
+    let mut by_band: Vec<Tally> = vec![Tally::default(); DISTANCE_BANDS.len()];
+    let mut correct_addresses = 0usize;
+    for (id_ban, m) in &best {
+        let correct = m
+            .id_parcelle
+            .as_ref()
+            .is_some_and(|pid| hidden[*id_ban].contains(pid));
+
+        let t = by_type.entry(m.match_type.as_str()).or_default();
+        t.predicted += 1;
+        let band = DISTANCE_BANDS
+            .iter()
+            .position(|(_, hi)| m.distance_m <= *hi)
+            .unwrap_or(DISTANCE_BANDS.len() - 1);
+        by_band[band].predicted += 1;
+        if correct {
+            t.correct += 1;
+            by_band[band].correct += 1;
+            correct_addresses += 1;
+        }
+    }
+
+    let scored = hidden.len();
+    let predicted = best.len();
+
+    // Recall per row = correct predictions of that row / all scored addresses (rows add up).
+    let mut type_rows = Vec::new();
+    for mt in SCORED_TYPES {
+        let t = by_type.get(mt.as_str()).copied().unwrap_or_default();
+        type_rows.push(format!(
+            "{},{},{},{:.2},{:.2}",
+            mt,
+            t.predicted,
+            t.correct,
+            pct(t.correct, t.predicted),
+            pct(t.correct, scored)
+        ));
+    }
+    let mut band_rows = Vec::new();
+    for ((label, _), t) in DISTANCE_BANDS.iter().zip(&by_band) {
+        band_rows.push(format!(
+            "{},{},{},{:.2},{:.2}",
+            label,
+            t.predicted,
+            t.correct,
+            pct(t.correct, t.predicted),
+            pct(t.correct, scored)
+        ));
+    }
+
+    let type_csv = args.output_dir.join("evaluation_by_match_type.csv");
+    std::fs::write(
+        &type_csv,
+        format!(
+            "match_type,predicted,correct,precision_pct,recall_pct\n{}\n",
+            type_rows.join("\n")
+        ),
+    )?;
+    let band_csv = args.output_dir.join("evaluation_by_distance_band.csv");
+    std::fs::write(
+        &band_csv,
+        format!(
+            "distance_band,predicted,correct,precision_pct,recall_pct\n{}\n",
+            band_rows.join("\n")
+        ),
+    )?;
+
+    let summary = EvaluationSummary {
+        generated_at: Utc::now().to_rfc3339(),
+        seed: args.seed,
+        holdout_fraction: args.holdout_fraction,
+        address_max_distance_m: config.address_max_distance_m,
+        fallback_max_distance_m: config.fallback_max_distance_m,
+        linked_addresses,
+        holdout_addresses,
+        holdout_unresolved,
+        scored_addresses: scored,
+        predicted_addresses: predicted,
+        correct_addresses,
+        precision_pct: pct(correct_addresses, predicted),
+        recall_pct: pct(correct_addresses, scored),
+    };
+    let json_out = args.output_dir.join("evaluation_summary.json");
+    let f = File::create(&json_out)?;
+    serde_json::to_writer_pretty(f, &summary)?;
+
+    info!(
+        scored_addresses = summary.scored_addresses,
+        predicted_addresses = summary.predicted_addresses,
+        correct_addresses = summary.correct_addresses,
+        precision_pct = summary.precision_pct,
+        recall_pct = summary.recall_pct,
+        "evaluation completed"
+    );
+    for p in [&type_csv, &band_csv, &json_out] {
+        info!(artifact=?p, "artifact");
+    }
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use crate::structures::{AddressInput, ParcelData, ParcelStore};
+use geo::Point;
 use rstar::{PointDistance, RTree, RTreeObject, AABB};
 
 pub struct ParcelNode {
@@ -78,18 +79,12 @@ impl<'a> AddressIndex<'a> {
         Self { addresses, tree }
     }
 
-    pub fn get(&self, idx: usize) -> &'a AddressInput {
-        &self.addresses[idx]
+    pub fn addresses(&self) -> &'a [AddressInput] {
+        self.addresses
     }
 
-    pub fn locate_in_envelope<'s>(
-        &'s self,
-        envelope: &'s AABB<[f64; 2]>,
-    ) -> impl Iterator<Item = &'a AddressInput> + 's {
-        let addresses = self.addresses;
-        self.tree
-            .locate_in_envelope(envelope)
-            .map(move |node| &addresses[node.idx])
+    pub fn get(&self, idx: usize) -> &'a AddressInput {
+        &self.addresses[idx]
     }
 
     pub fn locate_in_envelope_indices<'s>(
@@ -99,4 +94,16 @@ impl<'a> AddressIndex<'a> {
         self.tree.locate_in_envelope(envelope).map(|node| node.idx)
     }
 
+    /// Nearest address and its distance.
+    pub fn nearest(&self, p: &Point<f64>) -> Option<(&'a AddressInput, f64)> {
+        self.tree
+            .nearest_neighbor(&[p.x(), p.y()])
+            .map(|node| (self.get(node.idx), node.distance_2(&[p.x(), p.y()]).sqrt()))
+    }
+
+    pub fn count_within_distance(&self, p: &Point<f64>, radius: f64) -> usize {
+        self.tree
+            .locate_within_distance([p.x(), p.y()], radius * radius)
+            .count()
+    }
 }
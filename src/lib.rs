@@ -0,0 +1,33 @@
+//! Matching of BAN addresses to cadastral parcels.
+//!
+//! Library entry points: build `ParcelData` / `AddressInput` from `geo` types (coordinates in
+//! the working CRS of the department, see `crs`), a `MatchConfig` with `MatchConfig::builder()`,
+//! then open a `Matcher` session. Staging Parquet files are read with `loader` and match rows
+//! written with `writer::MatchWriter`.
+
+pub mod cog;
+pub mod confidence;
+pub mod crs;
+pub mod error;
+pub mod indexer;
+pub mod loader;
+pub mod matcher;
+pub mod osm;
+pub mod parcel_id;
+pub mod session;
+pub mod structures;
+pub mod writer;
+
+// `ban_cadastre::` paths in `test_support`, shared with the binary.
+#[cfg(test)]
+extern crate self as ban_cadastre;
+#[cfg(test)]
+mod test_support;
+
+pub use error::Error;
+pub use matcher::match_parcels_and_addresses_3_steps;
+pub use session::Matcher;
+pub use structures::{
+    AddressInput, CrossCommunePolicy, MatchConfig, MatchConfigBuilder, MatchOutput, MatchType,
+    ParcelData, ParcelGeometry, PositionPolicy,
+};
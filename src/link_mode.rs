@@ -1,14 +1,20 @@
 use crate::cli::LinkArgs;
-use crate::loader::{load_addresses, load_parcels};
-use crate::matcher::match_parcels_and_addresses_3_steps;
-use crate::structures::MatchConfig;
-use crate::writer::MatchWriter;
 use anyhow::Result;
+use ban_cadastre::confidence::load_scorer;
+use ban_cadastre::crs::{commune_department, department_crs, WorkingCrs, LAMBERT_93};
+use ban_cadastre::loader::{
+    load_addresses, load_buildings, load_parcels, normalize_address_communes,
+    normalize_parcel_communes,
+};
+use ban_cadastre::matcher::{match_parcels_and_addresses_3_steps, unresolved_links};
+use ban_cadastre::parcel_id::ParcelIdResolver;
+use ban_cadastre::structures::{MatchConfig, ParcelStore};
+use ban_cadastre::writer::MatchWriter;
 use std::time::Instant;
 
 use tracing::{info, warn};
 
-fn log_crs_sanity_addresses(xs: &[f64], ys: &[f64]) {
+fn log_crs_sanity_addresses(xs: &[f64], ys: &[f64], crs: &WorkingCrs) {
     if xs.is_empty() || ys.is_empty() {
         return;
     }
@@ -18,10 +24,28 @@ fn log_crs_sanity_addresses(xs: &[f64], ys: &[f64]) {
     if max_abs_x <= 200.0 && max_abs_y <= 100.0 {
         warn!(
             max_abs_x,
-            max_abs_y, "CRS sanity: coordinates look like degrees; expected EPSG:2154 meters"
+            max_abs_y,
+            crs = crs.code,
+            "CRS sanity: coordinates look like degrees; expected projected meters"
+        );
+        return;
+    }
+    let outside = xs
+        .iter()
+        .zip(ys)
+        .filter(|(x, y)| !crs.contains(**x, **y))
+        .count();
+    if outside * 2 > xs.len() {
+        warn!(
+            outside,
+            total = xs.len(),
+            crs = crs.code,
+            crs_name = crs.name,
+            "CRS sanity: most coordinates are outside the expected extent of the working CRS"
         );
     }
 }
+
 pub fn run_link(args: LinkArgs) -> Result<()> {
     info!("starting link mode");
     info!(input_addresses=?args.input_adresses, "input addresses");
@@ -35,15 +59,28 @@ pub fn run_link(args: LinkArgs) -> Result<()> {
     let start_load = Instant::now();
     let mut parcels = load_parcels(&args.input_parcelles)?;
     let mut addresses = load_addresses(&args.input_adresses)?;
+    let mut buildings = match &args.input_batiments {
+        Some(p) => Some(load_buildings(p)?),
+        None => None,
+    };
 
     info!(
         parcels = parcels.len(),
         addresses = addresses.len(),
+        buildings = buildings.as_ref().map(Vec::len),
         elapsed_ms = start_load.elapsed().as_millis(),
         "loaded inputs"
     );
 
-    // CRS sanity (heuristic)
+    // CRS sanity (heuristic), against the department's working CRS
+    let dept = args.dept.clone().or_else(|| {
+        addresses
+            .first()
+            .and_then(|a| commune_department(&a.code_insee))
+            .map(str::to_owned)
+    });
+    let crs = dept.as_deref().map(department_crs).unwrap_or(LAMBERT_93);
+    info!(dept=?dept, crs = crs.code, "working CRS");
     {
         let mut ax = Vec::with_capacity(addresses.len());
         let mut ay = Vec::with_capacity(addresses.len());
@@ -51,13 +88,33 @@ pub fn run_link(args: LinkArgs) -> Result<()> {
             ax.push(a.geom.x());
             ay.push(a.geom.y());
         }
-        log_crs_sanity_addresses(&ax, &ay);
+        log_crs_sanity_addresses(&ax, &ay, &crs);
+    }
+
+    let cog = args.cog.load()?;
+    if let Some(cog) = &cog {
+        let parcels_changed = normalize_parcel_communes(&mut parcels, cog);
+        let addresses_changed = normalize_address_communes(&mut addresses, cog);
+        if let Some(b) = buildings.as_mut() {
+            normalize_parcel_communes(b, cog);
+        }
+        info!(
+            parcels_changed,
+            addresses_changed, "code_insee normalized to the COG reference year"
+        );
     }
 
     if let Some(code) = &args.filter_commune {
+        let code = match &cog {
+            Some(cog) => cog.normalize(code).into_owned(),
+            None => code.clone(),
+        };
         info!(commune=%code, "filtering by commune");
-        parcels.retain(|p| p.code_insee == *code);
-        addresses.retain(|a| a.code_insee == *code);
+        parcels.retain(|p| p.code_insee == code);
+        addresses.retain(|a| a.code_insee == code);
+        if let Some(b) = buildings.as_mut() {
+            b.retain(|b| b.code_insee == code);
+        }
         info!(
             parcels = parcels.len(),
             addresses = addresses.len(),
@@ -79,22 +136,52 @@ pub fn run_link(args: LinkArgs) -> Result<()> {
         return Ok(());
     }
 
-    let config = MatchConfig {
+    let mut config = MatchConfig {
         address_max_distance_m: args.distance_threshold,
 
         // defaults for Step3
         fallback_max_distance_m: 1500.0,
         fallback_envelope_expand_m: 50.0,
+
+        emit_unmatched_addresses: args.emit_unmatched,
+        border_candidates: if args.candidates {
+            args.num_neighbors
+        } else {
+            1
+        },
+        preexisting_max_distance_m: args.preexisting_max_distance_m,
+        stacked_min_addresses: args.stacked_min_addresses,
+        exclude_stacked: args.exclude_stacked,
+        prefer_built_parcels: buildings.is_some(),
+        building_max_distance_m: args.building_max_distance_m,
+        confidence_scorer: load_scorer(args.confidence_model.as_deref())?,
+
+        ..MatchConfig::default()
     };
+    args.cross_commune.apply(&mut config);
+    args.positions.apply(&mut config);
 
     info!(?config, "running matcher");
     let start_match = Instant::now();
-    let matches = match_parcels_and_addresses_3_steps(&parcels, &addresses, &config);
+    let matches = match_parcels_and_addresses_3_steps(
+        &parcels,
+        &addresses,
+        buildings.as_ref().map(|b| b as &dyn ParcelStore),
+        &config,
+    );
     info!(
         elapsed_ms = start_match.elapsed().as_millis(),
         matches = matches.len(),
         "matching completed"
     );
+    let resolver = ParcelIdResolver::new(parcels.iter().map(|p| p.id.as_str()));
+    let unresolved = unresolved_links(&addresses, |pid| resolver.resolve(pid).is_some()).len();
+    if unresolved > 0 {
+        warn!(
+            unresolved_links = unresolved,
+            "BAN links to parcels outside the input parcels (not emitted)"
+        );
+    }
 
     let mut counts = std::collections::HashMap::new();
     for m in &matches {
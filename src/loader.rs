@@ -1,3 +1,4 @@
+use crate::cog::CogHistory;
 use crate::structures::{AddressInput, ParcelData, ParcelGeometry};
 use anyhow::{anyhow, Context, Result};
 use geo::Geometry;
@@ -18,6 +19,15 @@ fn get_string_or_long(row: &Row, idx: usize) -> Option<String> {
     }
 }
 
+fn non_empty(s: String) -> Option<String> {
+    let s_trim = s.trim().to_string();
+    if s_trim.is_empty() || s_trim.eq_ignore_ascii_case("null") {
+        None
+    } else {
+        Some(s_trim)
+    }
+}
+
 pub fn load_parcels(path: &Path) -> Result<Vec<ParcelData>> {
     let file =
         File::open(path).with_context(|| format!("Failed to open parcel file: {:?}", path))?;
@@ -62,6 +72,11 @@ pub fn load_parcels(path: &Path) -> Result<Vec<ParcelData>> {
     Ok(parcels)
 }
 
+/// Buildings (`batiments_<DEP>.parquet`) share the parcel staging schema (id, code_insee, geom).
+pub fn load_buildings(path: &Path) -> Result<Vec<ParcelData>> {
+    load_parcels(path)
+}
+
 pub fn load_addresses(path: &Path) -> Result<Vec<AddressInput>> {
     let file =
         File::open(path).with_context(|| format!("Failed to open address file: {:?}", path))?;
@@ -87,23 +102,52 @@ pub fn load_addresses(path: &Path) -> Result<Vec<AddressInput>> {
             _ => continue,
         };
 
-        let existing_link_raw = get_string_or_long(&row, 3);
-        let existing_link = existing_link_raw.and_then(|s| {
-            let s_trim = s.trim().to_string();
-            if s_trim.is_empty() || s_trim.eq_ignore_ascii_case("null") {
-                None
-            } else {
-                Some(s_trim)
-            }
-        });
+        let existing_link = get_string_or_long(&row, 3).and_then(non_empty);
+        // type_position / source_position: absent from staging files prepared before they were kept
+        let type_position = (row.len() > 4)
+            .then(|| get_string_or_long(&row, 4))
+            .flatten()
+            .and_then(non_empty);
+        let source_position = (row.len() > 5)
+            .then(|| get_string_or_long(&row, 5))
+            .flatten()
+            .and_then(non_empty);
 
         addresses.push(AddressInput {
             id,
             code_insee,
             geom,
             existing_link,
+            type_position,
+            source_position,
         });
     }
 
     Ok(addresses)
 }
+
+/// Rewrites parcel `code_insee` to the COG reference year; returns the number of rows changed.
+pub fn normalize_parcel_communes(parcels: &mut [ParcelData], cog: &CogHistory) -> usize {
+    let mut changed = 0;
+    for p in parcels {
+        let code = cog.normalize(&p.code_insee);
+        if code != p.code_insee.as_str() {
+            p.code_insee = code.into_owned();
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Rewrites address `code_insee` to the COG reference year; returns the number of rows changed.
+pub fn normalize_address_communes(addresses: &mut [AddressInput], cog: &CogHistory) -> usize {
+    let mut changed = 0;
+    for a in addresses {
+        let code = cog.normalize(&a.code_insee);
+        if code != a.code_insee.as_str() {
+            a.code_insee = code.into_owned();
+            changed += 1;
+        }
+    }
+    changed
+}
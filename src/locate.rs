@@ -0,0 +1,361 @@
+use crate::cli::LocateArgs;
+use anyhow::{anyhow, bail, Context, Result};
+use arrow::array::{Array, BooleanArray, Float64Array, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use ban_cadastre::crs::{commune_department, department_crs, WorkingCrs, LAMBERT_93};
+use ban_cadastre::loader::{load_addresses, load_parcels};
+use ban_cadastre::structures::MatchConfig;
+use ban_cadastre::Matcher;
+use geo::Point;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// CRS of the `locate` input coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PointCrs {
+    /// Longitude / latitude in degrees (EPSG:4326), projected to the working CRS
+    #[default]
+    Wgs84,
+    /// EPSG:2154 meters (metropolitan departments only)
+    Lambert93,
+}
+
+impl PointCrs {
+    fn default_columns(self) -> (&'static str, &'static str) {
+        match self {
+            PointCrs::Wgs84 => ("lon", "lat"),
+            PointCrs::Lambert93 => ("x", "y"),
+        }
+    }
+}
+
+/// Input point: id and raw coordinates (`None` when missing or not numeric).
+struct InputPoint {
+    id: String,
+    x: Option<f64>,
+    y: Option<f64>,
+}
+
+/// One output row: the point in the working CRS, its parcel and nearest address.
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Location {
+    id: String,
+    x: Option<f64>,
+    y: Option<f64>,
+    id_parcelle: Option<String>,
+    code_insee: Option<String>,
+    inside: Option<bool>,
+    distance_m: Option<f64>,
+    id_ban: Option<String>,
+    address_distance_m: Option<f64>,
+}
+
+/// Containing parcel (border included, distance 0), else nearest parcel, and nearest address;
+/// both within `max_distance_m`.
+fn locate_point(matcher: &Matcher, id: String, p: Point<f64>, max_distance_m: f64) -> Location {
+    let parcel = match matcher.parcel_at(&p) {
+        Some(parcel) => Some((parcel, 0.0)),
+        None => matcher.nearest_parcel(&p),
+    }
+    .filter(|(_, d)| *d <= max_distance_m);
+    let address = matcher
+        .nearest_address(&p)
+        .filter(|(_, d)| *d <= max_distance_m);
+    Location {
+        id,
+        x: Some(p.x()),
+        y: Some(p.y()),
+        id_parcelle: parcel.map(|(pa, _)| pa.id.clone()),
+        code_insee: parcel.map(|(pa, _)| pa.code_insee.clone()),
+        inside: parcel.map(|(_, d)| d == 0.0),
+        distance_m: parcel.map(|(_, d)| d),
+        id_ban: address.map(|(a, _)| a.id.clone()),
+        address_distance_m: address.map(|(_, d)| d),
+    }
+}
+
+fn read_csv_points(path: &Path, id_col: &str, x_col: &str, y_col: &str) -> Result<Vec<InputPoint>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open points file: {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    let delimiter = if header.contains(';') { b';' } else { b',' };
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(Cursor::new(header).chain(reader));
+
+    let headers = csv.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim() == name)
+            .ok_or_else(|| anyhow!("Points CSV {:?}: missing column {}", path, name))
+    };
+    let (id_idx, x_idx, y_idx) = (column(id_col)?, column(x_col)?, column(y_col)?);
+    let mut points = Vec::new();
+    for record in csv.records() {
+        let record = record.with_context(|| format!("Points CSV {:?}", path))?;
+        let number = |i: usize| record.get(i).and_then(|v| v.trim().parse().ok());
+        points.push(InputPoint {
+            id: record.get(id_idx).unwrap_or_default().trim().to_string(),
+            x: number(x_idx),
+            y: number(y_idx),
+        });
+    }
+    Ok(points)
+}
+
+fn read_parquet_points(
+    path: &Path,
+    id_col: &str,
+    x_col: &str,
+    y_col: &str,
+) -> Result<Vec<InputPoint>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open points file: {:?}", path))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .context("Failed to create parquet reader")?
+        .build()?;
+    let mut points = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        // Ids may be integers and coordinates strings or floats: cast to the output types.
+        let column = |name: &str, to: &DataType| -> Result<Arc<dyn Array>> {
+            let col = batch
+                .column_by_name(name)
+                .ok_or_else(|| anyhow!("Points Parquet {:?}: missing column {}", path, name))?;
+            Ok(cast(col, to)?)
+        };
+        let ids = column(id_col, &DataType::Utf8)?;
+        let xs = column(x_col, &DataType::Float64)?;
+        let ys = column(y_col, &DataType::Float64)?;
+        let ids = ids.as_any().downcast_ref::<StringArray>().unwrap();
+        let xs = xs.as_any().downcast_ref::<Float64Array>().unwrap();
+        let ys = ys.as_any().downcast_ref::<Float64Array>().unwrap();
+        for i in 0..batch.num_rows() {
+            let number = |a: &Float64Array| a.is_valid(i).then(|| a.value(i));
+            points.push(InputPoint {
+                id: if ids.is_valid(i) {
+                    ids.value(i).to_string()
+                } else {
+                    String::new()
+                },
+                x: number(xs),
+                y: number(ys),
+            });
+        }
+    }
+    Ok(points)
+}
+
+fn write_csv(path: &Path, rows: &[Location]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("Failed to create output file: {:?}", path))?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_parquet(path: &Path, rows: &[Location]) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("x", DataType::Float64, true),
+        Field::new("y", DataType::Float64, true),
+        Field::new("id_parcelle", DataType::Utf8, true),
+        Field::new("code_insee", DataType::Utf8, true),
+        Field::new("inside", DataType::Boolean, true),
+        Field::new("distance_m", DataType::Float64, true),
+        Field::new("id_ban", DataType::Utf8, true),
+        Field::new("address_distance_m", DataType::Float64, true),
+    ]));
+    let strings = |f: fn(&Location) -> Option<&str>| {
+        Arc::new(rows.iter().map(f).collect::<StringArray>()) as Arc<dyn Array>
+    };
+    let floats = |f: fn(&Location) -> Option<f64>| {
+        Arc::new(rows.iter().map(f).collect::<Float64Array>()) as Arc<dyn Array>
+    };
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            strings(|r| Some(r.id.as_str())),
+            floats(|r| r.x),
+            floats(|r| r.y),
+            strings(|r| r.id_parcelle.as_deref()),
+            strings(|r| r.code_insee.as_deref()),
+            Arc::new(rows.iter().map(|r| r.inside).collect::<BooleanArray>()),
+            floats(|r| r.distance_m),
+            strings(|r| r.id_ban.as_deref()),
+            floats(|r| r.address_distance_m),
+        ],
+    )?;
+    let file =
+        File::create(path).with_context(|| format!("Failed to create output file: {:?}", path))?;
+    let mut writer =
+        ArrowWriter::try_new(file, schema, None).context("Failed to create ArrowWriter")?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+}
+
+pub fn run_locate(args: LocateArgs) -> Result<()> {
+    info!(input=?args.input, input_crs=?args.input_crs, output=?args.output, "starting locate mode");
+
+    let (default_x, default_y) = args.input_crs.default_columns();
+    let x_col = args.x_column.as_deref().unwrap_or(default_x);
+    let y_col = args.y_column.as_deref().unwrap_or(default_y);
+    let points = if is_csv(&args.input) {
+        read_csv_points(&args.input, &args.id_column, x_col, y_col)?
+    } else {
+        read_parquet_points(&args.input, &args.id_column, x_col, y_col)?
+    };
+
+    let start_load = Instant::now();
+    let parcels = load_parcels(&args.input_parcelles)?;
+    let addresses = load_addresses(&args.input_adresses)?;
+    info!(
+        points = points.len(),
+        parcels = parcels.len(),
+        addresses = addresses.len(),
+        elapsed_ms = start_load.elapsed().as_millis(),
+        "loaded inputs"
+    );
+
+    let dept = args.dept.clone().or_else(|| {
+        parcels
+            .first()
+            .and_then(|p| commune_department(&p.code_insee))
+            .map(str::to_owned)
+    });
+    let crs: WorkingCrs = dept.as_deref().map(department_crs).unwrap_or(LAMBERT_93);
+    if args.input_crs == PointCrs::Lambert93 && crs != LAMBERT_93 {
+        bail!(
+            "Department {:?} is matched in {} ({}); give its points in WGS84",
+            dept,
+            crs.code,
+            crs.name
+        );
+    }
+    info!(dept=?dept, crs = crs.code, "working CRS");
+
+    let matcher = Matcher::new(&parcels, &addresses, MatchConfig::default())?;
+    let max_distance_m = args.max_distance_m.unwrap_or(f64::INFINITY);
+    let start_locate = Instant::now();
+    let rows: Vec<Location> = points
+        .into_par_iter()
+        .map(|pt| match (pt.x, pt.y) {
+            (Some(x), Some(y)) if x.is_finite() && y.is_finite() => {
+                let p = match args.input_crs {
+                    PointCrs::Wgs84 => crs.project(x, y).into(),
+                    PointCrs::Lambert93 => Point::new(x, y),
+                };
+                locate_point(&matcher, pt.id, p, max_distance_m)
+            }
+            _ => Location {
+                id: pt.id,
+                ..Location::default()
+            },
+        })
+        .collect();
+
+    let invalid = rows.iter().filter(|r| r.x.is_none()).count();
+    let inside = rows.iter().filter(|r| r.inside == Some(true)).count();
+    let without_parcel = rows.len() - invalid - rows.iter().filter(|r| r.inside.is_some()).count();
+    if invalid > 0 {
+        warn!(
+            invalid,
+            "points without numeric coordinates (empty output row)"
+        );
+    }
+    info!(
+        points = rows.len(),
+        inside,
+        without_parcel,
+        elapsed_ms = start_locate.elapsed().as_millis(),
+        "locate completed"
+    );
+
+    if let Some(parent) = args.output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if is_csv(&args.output) {
+        write_csv(&args.output, &rows)
+    } else {
+        write_parquet(&args.output, &rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{address, square};
+    use ban_cadastre::structures::{AddressInput, ParcelData};
+    use geo::point;
+
+    /// Parcel A = [0, 10]², address a1 at its centre.
+    fn fixture() -> (Vec<ParcelData>, Vec<AddressInput>) {
+        (
+            vec![square("A", "01001", 0.0, 0.0, 10.0)],
+            vec![address("a1", "01001", 5.0, 5.0)],
+        )
+    }
+
+    #[test]
+    fn point_on_the_border_is_inside() {
+        let (parcels, addresses) = fixture();
+        let matcher = Matcher::new(&parcels, &addresses, MatchConfig::default()).unwrap();
+        let on_border = locate_point(&matcher, "p1".into(), point!(x: 10.0, y: 5.0), 50.0);
+        assert_eq!(on_border.id_parcelle.as_deref(), Some("A"));
+        assert_eq!(
+            (on_border.inside, on_border.distance_m),
+            (Some(true), Some(0.0))
+        );
+        assert_eq!(on_border.address_distance_m, Some(5.0));
+    }
+
+    #[test]
+    fn point_outside_gets_the_nearest_parcel() {
+        let (parcels, addresses) = fixture();
+        let matcher = Matcher::new(&parcels, &addresses, MatchConfig::default()).unwrap();
+        let outside = locate_point(&matcher, "p2".into(), point!(x: 13.0, y: 5.0), 50.0);
+        assert_eq!(outside.id_parcelle.as_deref(), Some("A"));
+        assert_eq!(
+            (outside.inside, outside.distance_m),
+            (Some(false), Some(3.0))
+        );
+        assert_eq!(outside.id_ban.as_deref(), Some("a1"));
+    }
+
+    #[test]
+    fn max_distance_cuts_parcel_and_address_separately() {
+        let (parcels, addresses) = fixture();
+        let matcher = Matcher::new(&parcels, &addresses, MatchConfig::default()).unwrap();
+        // Parcel at 3 m, address at 8 m.
+        let p = point!(x: 13.0, y: 5.0);
+        let parcel_only = locate_point(&matcher, "p3".into(), p, 5.0);
+        assert_eq!(parcel_only.id_parcelle.as_deref(), Some("A"));
+        assert_eq!(parcel_only.id_ban, None);
+
+        let far = locate_point(&matcher, "p4".into(), p, 2.0);
+        assert_eq!((far.id_parcelle, far.id_ban), (None, None));
+        // The point itself is still reported.
+        assert_eq!((far.x, far.y), (Some(13.0), Some(5.0)));
+    }
+}
@@ -1,12 +1,17 @@
+#[cfg(feature = "polars-analysis")]
 mod analysis;
+mod calibrate;
 mod cli;
-mod indexer;
+mod evaluate;
 mod link_mode;
-mod loader;
-mod matcher;
+mod locate;
+mod osm_mode;
+#[cfg(all(feature = "download", feature = "duckdb-qa"))]
 mod pipeline;
-mod structures;
-mod writer;
+mod prepare;
+mod serve;
+#[cfg(test)]
+mod test_support;
 
 use clap::Parser;
 use cli::{Cli, Commands};
@@ -29,6 +34,7 @@ fn main() -> std::process::ExitCode {
             }
             std::process::ExitCode::from(0)
         }
+        #[cfg(all(feature = "download", feature = "duckdb-qa"))]
         Commands::Pipeline(args) => {
             let strict = args.strict;
             match pipeline::run_pipeline(args) {
@@ -55,6 +61,7 @@ fn main() -> std::process::ExitCode {
                 }
             }
         }
+        #[cfg(feature = "polars-analysis")]
         Commands::Analyze(args) => {
             let strict = args.strict;
             match analysis::run_analyze(args) {
@@ -81,6 +88,7 @@ fn main() -> std::process::ExitCode {
                 }
             }
         }
+        #[cfg(all(feature = "download", feature = "duckdb-qa"))]
         Commands::Status(args) => {
             if let Err(e) = pipeline::status::run_status(args) {
                 eprintln!("{:#}", e);
@@ -88,5 +96,54 @@ fn main() -> std::process::ExitCode {
             }
             std::process::ExitCode::from(0)
         }
+        Commands::Evaluate(args) => {
+            if let Err(e) = evaluate::run_evaluate(args) {
+                eprintln!("{:#}", e);
+                return std::process::ExitCode::from(1);
+            }
+            std::process::ExitCode::from(0)
+        }
+        Commands::Calibrate(args) => {
+            if let Err(e) = calibrate::run_calibrate(args) {
+                eprintln!("{:#}", e);
+                return std::process::ExitCode::from(1);
+            }
+            std::process::ExitCode::from(0)
+        }
+        Commands::Serve(args) => {
+            if let Err(e) = serve::run_serve(args) {
+                eprintln!("{:#}", e);
+                return std::process::ExitCode::from(1);
+            }
+            std::process::ExitCode::from(0)
+        }
+        Commands::Prepare(args) => {
+            if let Err(e) = prepare::run_prepare(args) {
+                eprintln!("{:#}", e);
+                return std::process::ExitCode::from(1);
+            }
+            std::process::ExitCode::from(0)
+        }
+        Commands::PreparePoints(args) => {
+            if let Err(e) = prepare::run_prepare_points(args) {
+                eprintln!("{:#}", e);
+                return std::process::ExitCode::from(1);
+            }
+            std::process::ExitCode::from(0)
+        }
+        Commands::Osm(args) => {
+            if let Err(e) = osm_mode::run_osm(args) {
+                eprintln!("{:#}", e);
+                return std::process::ExitCode::from(1);
+            }
+            std::process::ExitCode::from(0)
+        }
+        Commands::Locate(args) => {
+            if let Err(e) = locate::run_locate(args) {
+                eprintln!("{:#}", e);
+                return std::process::ExitCode::from(1);
+            }
+            std::process::ExitCode::from(0)
+        }
     }
 }
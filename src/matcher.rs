@@ -1,35 +1,72 @@
+use crate::confidence::{ConfidenceFeatures, ConfidenceScorer, DENSITY_RADIUS_M};
 use crate::indexer::{AddressIndex, DepartmentIndex};
-use crate::structures::{AddressInput, MatchConfig, MatchOutput, MatchType, ParcelData, ParcelStore};
-use geo::{Point};
+use crate::parcel_id::ParcelIdResolver;
+use crate::structures::{
+    AddressInput, CrossCommunePolicy, MatchConfig, MatchOutput, MatchType, ParcelData, ParcelStore,
+    PositionPolicy, AMBIGUITY_MARGIN_CAP_M,
+};
+use geo::Point;
 use rayon::prelude::*;
-use rstar::{ PointDistance, AABB};
+use rstar::{PointDistance, AABB};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 
-
 pub type PreexistingMap = HashMap<String, Vec<MatchOutput>>;
 
+/// Parcel ids of a BAN `cad_parcelles` value (separators `;`, `|`, `,`).
+pub fn split_links(links: &str) -> impl Iterator<Item = &str> {
+    links
+        .split([';', '|', ','])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+/// PreExisting row of the BAN link `link`, with the real point→polygon distance.
+/// Links farther than `max_distance_m` are emitted as `PreExistingFar`.
+pub fn preexisting_output(
+    addr: &AddressInput,
+    link: &str,
+    parcel: &ParcelData,
+    max_distance_m: f64,
+) -> MatchOutput {
+    let d = parcel.geom.distance_to_point(&addr.geom);
+    let (match_type, out_dist) = if !d.is_finite() || d <= INSIDE_EPS_M {
+        (MatchType::PreExisting, 0.0_f32)
+    } else if d <= max_distance_m {
+        (MatchType::PreExisting, d as f32)
+    } else {
+        (MatchType::PreExistingFar, d as f32)
+    };
+    let mut m = MatchOutput::new(
+        addr.id.clone(),
+        Some(parcel.id.clone()),
+        out_dist,
+        match_type,
+    );
+    m.id_parcelle_ban = (link != parcel.id).then(|| link.to_owned());
+    m
+}
+
+/// BAN `cad_parcelles` links, keyed by Etalab parcel id (link variants normalized by `resolver`).
+/// Links to parcels outside `known_parcels` are skipped here, see `unresolved_links`.
 pub fn build_preexisting_map(
     addresses: &[AddressInput],
-    known_parcels: &HashSet<&str>,
+    known_parcels: &HashMap<&str, &ParcelData>,
+    resolver: &ParcelIdResolver,
+    max_distance_m: f64,
 ) -> PreexistingMap {
     let mut map: PreexistingMap = HashMap::new();
 
     for addr in addresses {
         if let Some(links) = &addr.existing_link {
-            for parcel_id in links.split(|c| c == ';' || c == '|' || c == ',') {
-                let pid = parcel_id.trim();
-                if pid.is_empty() {
+            for link in split_links(links) {
+                let Some(pid) = resolver.resolve(link).map(|r| r.id()) else {
                     continue;
-                }
-                if known_parcels.contains(pid) {
-                    let m = MatchOutput::new(
-                        addr.id.clone(),
-                        Some(pid.to_owned()),
-                        0.0,
-                        MatchType::PreExisting,
-                    );
-                    map.entry(pid.to_owned()).or_default().push(m);
+                };
+                if let Some(parcel) = known_parcels.get(pid) {
+                    map.entry(pid.to_owned())
+                        .or_default()
+                        .push(preexisting_output(addr, link, parcel, max_distance_m));
                 }
             }
         }
@@ -38,6 +75,21 @@ pub fn build_preexisting_map(
     map
 }
 
+/// BAN links (address, parcel id) whose parcel is not known to the matcher,
+/// typically parcels of another department (`is_known` should accept id variants,
+/// see `ParcelIdResolver`).
+pub fn unresolved_links<'a>(
+    addresses: impl IntoIterator<Item = &'a AddressInput>,
+    is_known: impl Fn(&str) -> bool,
+) -> Vec<(&'a AddressInput, &'a str)> {
+    addresses
+        .into_iter()
+        .filter_map(|addr| Some((addr, addr.existing_link.as_deref()?)))
+        .flat_map(|(addr, links)| split_links(links).map(move |pid| (addr, pid)))
+        .filter(|(_, pid)| !is_known(pid))
+        .collect()
+}
+
 fn expand_aabb(env: &AABB<[f64; 2]>, margin: f64) -> AABB<[f64; 2]> {
     let lo = env.lower();
     let hi = env.upper();
@@ -49,25 +101,272 @@ fn expand_aabb(env: &AABB<[f64; 2]>, margin: f64) -> AABB<[f64; 2]> {
 
 const INSIDE_EPS_M: f64 = 0.01;
 
-fn is_inside_or_on_border(parcel: &ParcelData, p: &Point<f64>) -> bool {
+pub(crate) fn is_inside_or_on_border(parcel: &ParcelData, p: &Point<f64>) -> bool {
     // 0 quand inside OU sur la frontière
     let d = parcel.geom.distance_to_point(p);
     d.is_finite() && d <= INSIDE_EPS_M
 }
 
+/// Confidence removed from links emitted under `CrossCommunePolicy::Penalize`.
+const CROSS_COMMUNE_CONFIDENCE_PENALTY: u32 = 20;
 
+fn is_cross_commune(addr: &AddressInput, parcel: &ParcelData) -> bool {
+    !addr.code_insee.is_empty()
+        && !parcel.code_insee.is_empty()
+        && addr.code_insee != parcel.code_insee
+}
+
+/// Distance used to rank a candidate under `policy` (None = candidate excluded).
+fn rank_distance(policy: CrossCommunePolicy, cross: bool, d: f64, penalty_m: f64) -> Option<f64> {
+    match (policy, cross) {
+        (_, false) | (CrossCommunePolicy::Allow, true) => Some(d),
+        (CrossCommunePolicy::Penalize, true) => Some(d + penalty_m),
+        (CrossCommunePolicy::Forbid, true) => None,
+    }
+}
+
+/// Nearest parcel (exact point→polygon distance), without distance limit.
+pub(crate) fn nearest_parcel<'a>(
+    parcel_index: &DepartmentIndex<'a>,
+    p: &Point<f64>,
+) -> Option<(&'a ParcelData, f64)> {
+    let xy = [p.x(), p.y()];
+    let mut best: Option<(&ParcelData, f64)> = None;
 
+    for node in parcel_index.tree.nearest_neighbor_iter(&xy) {
+        // AABB distance is a lower bound of the polygon distance
+        if let Some((_, bd)) = best {
+            if node.distance_2(&xy) > bd * bd {
+                break;
+            }
+        }
+        let parcel = parcel_index.get_parcel(node.idx);
+        let d = parcel.geom.distance_to_point(p);
+        if !d.is_finite() {
+            continue;
+        }
+        if best.map(|(_, bd)| d < bd).unwrap_or(true) {
+            best = Some((parcel, d));
+        }
+    }
 
+    best
+}
+
+/// Stack size per address (addresses sharing its exact coordinate), when >= `min_size`.
+/// Typical of BAN default positions (town hall, street centroid).
+fn detect_stacked(addresses: &[AddressInput], min_size: usize) -> Vec<Option<u32>> {
+    if min_size == 0 {
+        return vec![None; addresses.len()];
+    }
+    let key = |a: &AddressInput| (a.geom.x().to_bits(), a.geom.y().to_bits());
+    let mut counts: HashMap<(u64, u64), u32> = HashMap::new();
+    for a in addresses {
+        *counts.entry(key(a)).or_default() += 1;
+    }
+    addresses
+        .iter()
+        .map(|a| Some(counts[&key(a)]).filter(|&n| n as usize >= min_size))
+        .collect()
+}
+
+/// Buildings near each address, for `MatchConfig::prefer_built_parcels`.
+struct BuildingContext<'a> {
+    /// Parcel hosts at least one building (building interior point inside the parcel).
+    parcel_is_built: Vec<bool>,
+    /// Per address: (host parcel idx, building id) within `building_max_distance_m`,
+    /// nearest building first, one entry per parcel.
+    near_built: Vec<Vec<(usize, &'a str)>>,
+}
+
+impl<'a> BuildingContext<'a> {
+    fn build(
+        parcel_index: &DepartmentIndex,
+        buildings: &'a dyn ParcelStore,
+        addresses: &[AddressInput],
+        max_distance_m: f64,
+    ) -> Self {
+        let hosts: Vec<Option<usize>> = (0..buildings.len())
+            .into_par_iter()
+            .map(|idx| {
+                let p = buildings.get_parcel(idx).geom.interior_point()?;
+                parcel_index
+                    .tree
+                    .locate_all_at_point(&[p.x(), p.y()])
+                    .map(|node| node.idx)
+                    .find(|&idx| is_inside_or_on_border(parcel_index.get_parcel(idx), &p))
+            })
+            .collect();
+
+        let mut parcel_is_built = vec![false; parcel_index.store.len()];
+        for idx in hosts.iter().flatten() {
+            parcel_is_built[*idx] = true;
+        }
+
+        let building_index = DepartmentIndex::build(buildings);
+        let max2 = max_distance_m * max_distance_m;
+        let near_built = addresses
+            .par_iter()
+            .map(|addr| {
+                let xy = [addr.geom.x(), addr.geom.y()];
+                let mut near: Vec<(usize, &'a str, f64)> = Vec::new();
+                for node in building_index.tree.nearest_neighbor_iter(&xy) {
+                    if node.distance_2(&xy) > max2 {
+                        break;
+                    }
+                    let Some(host) = hosts[node.idx] else {
+                        continue;
+                    };
+                    let b = building_index.get_parcel(node.idx);
+                    let d = b.geom.distance_to_point(&addr.geom);
+                    if d.is_finite() && d <= max_distance_m {
+                        near.push((host, b.id.as_str(), d));
+                    }
+                }
+                near.sort_by(|a, b| a.2.total_cmp(&b.2));
+                let mut seen = HashSet::new();
+                near.into_iter()
+                    .filter(|(host, _, _)| seen.insert(*host))
+                    .map(|(host, id, _)| (host, id))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            parcel_is_built,
+            near_built,
+        }
+    }
+
+    /// Nearest building near the address carried by the parcel.
+    fn building_for(&self, a_idx: usize, parcel_idx: usize) -> Option<&'a str> {
+        self.near_built[a_idx]
+            .iter()
+            .find(|(host, _)| *host == parcel_idx)
+            .map(|(_, id)| *id)
+    }
+
+    /// The address falls in an unbuilt parcel while a built parcel is nearby.
+    fn skips_inside(&self, a_idx: usize, parcel_idx: usize) -> bool {
+        !self.parcel_is_built[parcel_idx] && !self.near_built[a_idx].is_empty()
+    }
+}
+
+/// Scorer features for each match (None when the parcel or the address is unknown).
+pub fn confidence_features(
+    matches: &[MatchOutput],
+    known_parcels: &HashMap<&str, &ParcelData>,
+    addresses: &[AddressInput],
+    address_index: &AddressIndex,
+) -> Vec<Option<ConfidenceFeatures>> {
+    let addr_by_id: HashMap<&str, &AddressInput> =
+        addresses.iter().map(|a| (a.id.as_str(), a)).collect();
+
+    matches
+        .par_iter()
+        .map(|m| {
+            let parcel = known_parcels.get(m.id_parcelle.as_deref()?)?;
+            let addr = addr_by_id.get(m.id_ban.as_str())?;
+            Some(ConfidenceFeatures {
+                match_type: m.match_type.clone(),
+                distance_m: m.distance_m,
+                margin_m: m.margin_m,
+                // the address itself is within the radius
+                address_density: address_index
+                    .count_within_distance(&addr.geom, DENSITY_RADIUS_M)
+                    .saturating_sub(1),
+                parcel_area_m2: parcel.geom.area_m2(),
+                cross_commune: is_cross_commune(addr, parcel),
+            })
+        })
+        .collect()
+}
+
+/// The low-precision position penalty is not a scorer feature: it is applied again on top
+/// of the calibrated probability.
+fn rescore_confidence(
+    matches: &mut [MatchOutput],
+    scorer: &dyn ConfidenceScorer,
+    known_parcels: &HashMap<&str, &ParcelData>,
+    addresses: &[AddressInput],
+    address_index: &AddressIndex,
+    config: &MatchConfig,
+) {
+    let features = confidence_features(matches, known_parcels, addresses, address_index);
+    let low_precision: HashSet<&str> = addresses
+        .iter()
+        .filter(|a| a.is_low_precision(&config.low_precision_positions))
+        .map(|a| a.id.as_str())
+        .collect();
+    matches
+        .par_iter_mut()
+        .zip(features.par_iter())
+        .for_each(|(m, f)| {
+            if let Some(p) = f.as_ref().and_then(|f| scorer.probability(f)) {
+                m.confidence = (p.clamp(0.0, 1.0) * 100.0).round() as u32;
+                if matches!(m.match_type, MatchType::Inside | MatchType::BorderNear) {
+                    let low = low_precision.contains(m.id_ban.as_str());
+                    apply_position_penalty(m, config.low_precision_policy, low);
+                }
+            }
+        });
+}
+
+fn apply_cross_commune_penalty(m: &mut MatchOutput, policy: CrossCommunePolicy, cross: bool) {
+    if cross && policy == CrossCommunePolicy::Penalize {
+        m.confidence = m
+            .confidence
+            .saturating_sub(CROSS_COMMUNE_CONFIDENCE_PENALTY);
+    }
+}
+
+/// Confidence removed from Inside / BorderNear links of low-precision positions (`Penalize`).
+const LOW_PRECISION_CONFIDENCE_PENALTY: u32 = 20;
+
+fn apply_position_penalty(m: &mut MatchOutput, policy: PositionPolicy, low_precision: bool) {
+    if low_precision && policy == PositionPolicy::Penalize {
+        m.confidence = m
+            .confidence
+            .saturating_sub(LOW_PRECISION_CONFIDENCE_PENALTY);
+    }
+}
+
+/// `buildings` (cadastre `batiments` layer) is only used with `config.prefer_built_parcels`.
 pub fn match_parcels_and_addresses_3_steps(
     parcels: &dyn ParcelStore,
     addresses: &[AddressInput],
+    buildings: Option<&dyn ParcelStore>,
     config: &MatchConfig,
 ) -> Vec<MatchOutput> {
-    let known_parcels: HashSet<&str> = parcels.iter().map(|p| p.id.as_str()).collect();
-    let preexisting_map = build_preexisting_map(addresses, &known_parcels);
-
     let parcel_index = DepartmentIndex::build(parcels);
     let address_index = AddressIndex::build(addresses);
+    match_indexed(&parcel_index, &address_index, buildings, config)
+}
+
+/// `match_parcels_and_addresses_3_steps` over indexes built by the caller (`Matcher` session).
+pub(crate) fn match_indexed(
+    parcel_index: &DepartmentIndex<'_>,
+    address_index: &AddressIndex<'_>,
+    buildings: Option<&dyn ParcelStore>,
+    config: &MatchConfig,
+) -> Vec<MatchOutput> {
+    let parcels = parcel_index.store;
+    let addresses = address_index.addresses();
+    let known_parcels: HashMap<&str, &ParcelData> =
+        parcels.iter().map(|p| (p.id.as_str(), p)).collect();
+    let resolver = ParcelIdResolver::new(known_parcels.keys().copied());
+    let preexisting_map = build_preexisting_map(
+        addresses,
+        &known_parcels,
+        &resolver,
+        config.preexisting_max_distance_m,
+    );
+
+    let stacked = detect_stacked(addresses, config.stacked_min_addresses);
+    let excluded_stacked = |a_idx: usize| config.exclude_stacked && stacked[a_idx].is_some();
+    let building_ctx = buildings.filter(|_| config.prefer_built_parcels).map(|b| {
+        BuildingContext::build(parcel_index, b, addresses, config.building_max_distance_m)
+    });
 
     // --- Step 1: INSIDE + PRE_EXISTING ---
     let step1_results: Vec<Vec<MatchOutput>> = (0..parcels.len())
@@ -84,17 +383,40 @@ pub fn match_parcels_and_addresses_3_steps(
                 }
             }
 
-            for addr in address_index.locate_in_envelope(&parcel.envelope) {
-                if strict_addr_ids.contains(&addr.id) {
+            for a_idx in address_index.locate_in_envelope_indices(&parcel.envelope) {
+                let addr = address_index.get(a_idx);
+                if strict_addr_ids.contains(&addr.id) || excluded_stacked(a_idx) {
+                    continue;
+                }
+                let cross = is_cross_commune(addr, parcel);
+                if cross && config.inside_cross_commune == CrossCommunePolicy::Forbid {
+                    continue;
+                }
+                let low_precision = addr.is_low_precision(&config.low_precision_positions);
+                if low_precision && config.low_precision_policy == PositionPolicy::Exclude {
+                    continue;
+                }
+                // Empty strip next to a built parcel: left to Step 2
+                if building_ctx
+                    .as_ref()
+                    .is_some_and(|b| b.skips_inside(a_idx, idx))
+                {
                     continue;
                 }
                 if is_inside_or_on_border(parcel, &addr.geom) {
-                    out.push(MatchOutput::new(
+                    let mut m = MatchOutput::new(
                         addr.id.clone(),
                         Some(parcel.id.clone()),
                         0.0,
                         MatchType::Inside,
-                    ));
+                    );
+                    m.id_batiment = building_ctx
+                        .as_ref()
+                        .and_then(|b| b.building_for(a_idx, idx))
+                        .map(str::to_owned);
+                    apply_cross_commune_penalty(&mut m, config.inside_cross_commune, cross);
+                    apply_position_penalty(&mut m, config.low_precision_policy, low_precision);
+                    out.push(m);
                 }
             }
 
@@ -102,8 +424,8 @@ pub fn match_parcels_and_addresses_3_steps(
         })
         .collect();
 
-    let mut all_matches: Vec<MatchOutput> = Vec::new();
-    all_matches.reserve(step1_results.iter().map(|v| v.len()).sum::<usize>());
+    let mut all_matches: Vec<MatchOutput> =
+        Vec::with_capacity(step1_results.iter().map(|v| v.len()).sum::<usize>());
     for mut v in step1_results {
         all_matches.append(&mut v);
     }
@@ -115,8 +437,11 @@ pub fn match_parcels_and_addresses_3_steps(
     for (idx, p) in parcels.iter().enumerate() {
         parcel_idx_by_id.insert(p.id.clone(), idx);
     }
-    // Mark parcels matched from Step1
-    for m in &all_matches {
+    // Mark parcels matched from Step1 (a far BAN link does not prevent Step 3)
+    for m in all_matches
+        .iter()
+        .filter(|m| m.match_type != MatchType::PreExistingFar)
+    {
         if let Some(pid) = &m.id_parcelle {
             if let Some(&idx) = parcel_idx_by_id.get(pid) {
                 parcel_has_match[idx] = true;
@@ -125,11 +450,20 @@ pub fn match_parcels_and_addresses_3_steps(
     }
 
     // STEP 2 (address-centric): BORDER_NEAR, 0 < d <= address_max_distance_m
+    // Candidate mode keeps the `border_candidates` nearest parcels (rank 1 = best).
+    let k = config.border_candidates.max(1);
     let step2_results: Vec<MatchOutput> = addresses
         .par_iter()
-        .filter_map(|addr| {
-
-            let mut best: Option<(&ParcelData, f64)> = None;
+        .enumerate()
+        .flat_map_iter(|(a_idx, addr)| {
+            // (parcel, real distance, ranking distance, cross-commune, building)
+            let low_precision = addr.is_low_precision(&config.low_precision_positions);
+            if excluded_stacked(a_idx)
+                || (low_precision && config.low_precision_policy == PositionPolicy::Exclude)
+            {
+                return Vec::new();
+            }
+            let mut candidates: Vec<(&ParcelData, f64, f64, bool, Option<&str>)> = Vec::new();
             let point_coords = [addr.geom.x(), addr.geom.y()];
             let thr = config.address_max_distance_m;
             let thr2 = thr * thr;
@@ -147,26 +481,58 @@ pub fn match_parcels_and_addresses_3_steps(
                 if d <= INSIDE_EPS_M || d > thr {
                     continue;
                 }
-                if best
-                    .map(|(_, bd)| d.total_cmp(&bd) == Ordering::Less)
-                    .unwrap_or(true)
-                {
-                    best = Some((p, d));
-                }
+                let cross = is_cross_commune(addr, p);
+                let Some(rank) = rank_distance(
+                    config.border_cross_commune,
+                    cross,
+                    d,
+                    config.cross_commune_penalty_m,
+                ) else {
+                    continue;
+                };
+                let building = building_ctx
+                    .as_ref()
+                    .and_then(|b| b.building_for(a_idx, node.idx));
+                candidates.push((p, d, rank, cross, building));
             }
 
-            let (p, d) = best?;
-            Some(MatchOutput::new(
-                addr.id.clone(),
-                Some(p.id.clone()),
-                d as f32,
-                MatchType::BorderNear,
-            ))
+            // stable sort: à égalité, l'ordre de parcours de l'index est conservé
+            // (parcelles bâties d'abord si prefer_built_parcels)
+            candidates.sort_by(|a, b| b.4.is_some().cmp(&a.4.is_some()).then(a.2.total_cmp(&b.2)));
+            // Runner-up of the same class (built / unbuilt) as the retained parcel: a built parcel
+            // preferred over a closer unbuilt one is not ambiguous.
+            let runner_up = candidates.split_first().and_then(|(best, rest)| {
+                rest.iter()
+                    .find(|c| c.4.is_some() == best.4.is_some())
+                    .map(|second| (second.0.id.clone(), (second.2 - best.2).max(0.0) as f32))
+            });
+            candidates.truncate(k);
+
+            candidates
+                .into_iter()
+                .enumerate()
+                .map(|(i, (p, d, _, cross, building))| {
+                    let mut m = MatchOutput::new(
+                        addr.id.clone(),
+                        Some(p.id.clone()),
+                        d as f32,
+                        MatchType::BorderNear,
+                    );
+                    m.rank = (i + 1) as u32;
+                    m.id_batiment = building.map(str::to_owned);
+                    if i == 0 {
+                        m = m.with_runner_up(runner_up.clone());
+                    }
+                    apply_cross_commune_penalty(&mut m, config.border_cross_commune, cross);
+                    apply_position_penalty(&mut m, config.low_precision_policy, low_precision);
+                    m
+                })
+                .collect::<Vec<_>>()
         })
         .collect();
 
-    // Add step2 matches + mark parcels matched
-    for m in &step2_results {
+    // Add step2 matches + mark parcels matched (best candidate only)
+    for m in step2_results.iter().filter(|m| m.rank == 1) {
         if let Some(pid) = &m.id_parcelle {
             if let Some(&idx) = parcel_idx_by_id.get(pid) {
                 parcel_has_match[idx] = true;
@@ -191,34 +557,44 @@ pub fn match_parcels_and_addresses_3_steps(
             // - on élargit progressivement l'AABB de la parcelle
             // - on évalue TOUTES les adresses dans la fenêtre (une fois)
             // - on s'arrête quand r >= best_dist (garantit le plus proche)
+            //   et que le second est connu exactement, ou à plus de AMBIGUITY_MARGIN_CAP_M
 
             let dmax = config.fallback_max_distance_m;
             let mut r = config.fallback_envelope_expand_m.max(5.0);
 
             let mut seen: HashSet<usize> = HashSet::with_capacity(256);
             let mut best_idx: Option<usize> = None;
+            // best_dist = distance de classement (pénalité cross-commune incluse), best_real = distance réelle
             let mut best_dist: f64 = f64::INFINITY;
+            let mut best_real: f64 = f64::INFINITY;
+            let mut best_cross = false;
             let mut best_addr_id: Option<String> = None;
+            // second (runner-up), distance de classement
+            let mut second_dist: f64 = f64::INFINITY;
+            let mut second_addr_id: Option<String> = None;
 
             while r <= dmax {
                 let env = expand_aabb(&parcel.envelope, r);
                 let mut any_new = false;
 
                 for a_idx in address_index.locate_in_envelope_indices(&env) {
-
                     if !seen.insert(a_idx) {
                         continue;
                     }
                     any_new = true;
+                    if excluded_stacked(a_idx) {
+                        continue;
+                    }
 
                     let addr = address_index.get(a_idx);
                     // Pruning (borne inférieure): distance(point, AABB(parcel)) <= distance(point, polygon)
-                    // Si la borne inférieure ne peut pas battre best_dist, inutile de calculer la distance au polygone.
+                    // Si la borne inférieure ne peut pas battre le second (ou best_dist + marge max),
+                    // inutile de calculer la distance au polygone.
                     if best_idx.is_some() && best_dist.is_finite() {
                         let pxy = [addr.geom.x(), addr.geom.y()];
                         let lb2 = parcel.envelope.distance_2(&pxy);
-                        let bd2 = best_dist * best_dist;
-                        if lb2 >= bd2 {
+                        let bound = second_dist.min(best_dist + AMBIGUITY_MARGIN_CAP_M);
+                        if lb2 >= bound * bound {
                             continue;
                         }
                     }
@@ -226,11 +602,20 @@ pub fn match_parcels_and_addresses_3_steps(
                     if !d.is_finite() || d > dmax {
                         continue;
                     }
+                    let cross = is_cross_commune(addr, parcel);
+                    let Some(rank) = rank_distance(
+                        config.fallback_cross_commune,
+                        cross,
+                        d,
+                        config.cross_commune_penalty_m,
+                    ) else {
+                        continue;
+                    };
 
                     let better = if best_idx.is_none() {
                         true
                     } else {
-                        match d.total_cmp(&best_dist) {
+                        match rank.total_cmp(&best_dist) {
                             Ordering::Less => true,
                             Ordering::Equal => {
                                 // tie-break déterministe
@@ -244,14 +629,25 @@ pub fn match_parcels_and_addresses_3_steps(
                     };
 
                     if better {
-                        best_dist = d;
+                        if best_idx.is_some() {
+                            second_dist = best_dist;
+                            second_addr_id = best_addr_id.take();
+                        }
+                        best_dist = rank;
+                        best_real = d;
+                        best_cross = cross;
                         best_idx = Some(a_idx);
                         best_addr_id = Some(addr.id.clone());
+                    } else if rank < second_dist {
+                        second_dist = rank;
+                        second_addr_id = Some(addr.id.clone());
                     }
                 }
 
-                // condition d'arrêt: si best_dist <= r, aucune adresse hors env(r) ne peut battre best_dist
-                if best_idx.is_some() && best_dist <= r {
+                // condition d'arrêt: si best_dist <= r, aucune adresse hors env(r) ne peut battre best_dist ;
+                // idem pour le second tant qu'il reste sous best_dist + marge max.
+                let closing = second_dist.min(best_dist + AMBIGUITY_MARGIN_CAP_M);
+                if best_idx.is_some() && closing <= r {
                     break;
                 }
 
@@ -261,11 +657,11 @@ pub fn match_parcels_and_addresses_3_steps(
 
                 // croissance:
                 // - par défaut: double
-                // - si on a déjà un best_dist, faire un "closing pass" direct à r = best_dist
+                // - si on a déjà un best_dist, faire un "closing pass" direct à r = closing
                 //   (plus rapide que de continuer à doubler jusqu'à le dépasser).
                 let mut next_r = (r * 2.0).min(dmax);
-                if best_idx.is_some() && best_dist.is_finite() && best_dist > r {
-                    next_r = best_dist.min(dmax);
+                if best_idx.is_some() && closing.is_finite() && closing > r {
+                    next_r = closing.min(dmax);
                 }
                 if next_r <= r {
                     break;
@@ -277,25 +673,469 @@ pub fn match_parcels_and_addresses_3_steps(
 
             let addr = address_index.get(a_idx);
             // Si Step 3 découvre un point "Inside", on le sort comme Inside (au lieu de FallbackNearest).
-            let (match_type, out_dist) = if best_dist <= INSIDE_EPS_M {
+            // ... sauf bande vide écartée par prefer_built_parcels, position imprécise exclue
+            // ou lien cross-commune interdit en Inside (Step 1).
+            let skipped_inside = building_ctx
+                .as_ref()
+                .is_some_and(|b| b.skips_inside(a_idx, idx))
+                || (config.low_precision_policy == PositionPolicy::Exclude
+                    && addr.is_low_precision(&config.low_precision_positions))
+                || (config.inside_cross_commune == CrossCommunePolicy::Forbid && best_cross);
+            let (match_type, out_dist) = if best_real <= INSIDE_EPS_M && !skipped_inside {
                 (MatchType::Inside, 0.0_f32)
             } else {
-                (MatchType::FallbackNearest, best_dist as f32)
+                (MatchType::FallbackNearest, best_real as f32)
             };
 
+            // Second exact seulement s'il est dans la fenêtre finale.
+            let runner_up = second_addr_id
+                .filter(|_| second_dist <= r)
+                .map(|id| (id, (second_dist - best_dist) as f32));
 
-            Some(MatchOutput::new(
+            let mut m = MatchOutput::new(
                 addr.id.clone(),
                 Some(parcel.id.clone()),
                 out_dist,
                 match_type,
-            ))
+            )
+            .with_runner_up(runner_up);
+            if m.match_type == MatchType::Inside {
+                apply_cross_commune_penalty(&mut m, config.inside_cross_commune, best_cross);
+                let low_precision = addr.is_low_precision(&config.low_precision_positions);
+                apply_position_penalty(&mut m, config.low_precision_policy, low_precision);
+            } else {
+                apply_cross_commune_penalty(&mut m, config.fallback_cross_commune, best_cross);
+            }
+            Some(m)
         })
         .collect();
 
     all_matches.extend(step3_results);
+
+    // Address completion (optional): one None row per address without any parcel,
+    // with the nearest parcel as a hint.
+    if config.emit_unmatched_addresses {
+        let none_rows: Vec<MatchOutput> = {
+            let matched_addr_ids: HashSet<&str> =
+                all_matches.iter().map(|m| m.id_ban.as_str()).collect();
+            addresses
+                .par_iter()
+                .filter(|addr| !matched_addr_ids.contains(addr.id.as_str()))
+                .map(|addr| {
+                    let nearest = nearest_parcel(parcel_index, &addr.geom)
+                        .map(|(p, d)| (p.id.clone(), d as f32));
+                    MatchOutput::unmatched(addr.id.clone(), nearest)
+                })
+                .collect()
+        };
+        all_matches.extend(none_rows);
+    }
+
+    let stack_by_id: HashMap<&str, u32> = addresses
+        .iter()
+        .zip(&stacked)
+        .filter_map(|(a, s)| s.map(|n| (a.id.as_str(), n)))
+        .collect();
+    if !stack_by_id.is_empty() {
+        for m in all_matches.iter_mut() {
+            m.stack_size = stack_by_id.get(m.id_ban.as_str()).copied();
+        }
+    }
+
+    if let Some(scorer) = &config.confidence_scorer {
+        rescore_confidence(
+            &mut all_matches,
+            scorer.as_ref(),
+            &known_parcels,
+            addresses,
+            address_index,
+            config,
+        );
+    }
+
     all_matches
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{address, square};
+
+    fn run(
+        parcels: Vec<ParcelData>,
+        addresses: &[AddressInput],
+        config: &MatchConfig,
+    ) -> Vec<MatchOutput> {
+        match_parcels_and_addresses_3_steps(&parcels, addresses, None, config)
+    }
+
+    fn rows_of<'m>(rows: &'m [MatchOutput], id_ban: &str) -> Vec<&'m MatchOutput> {
+        rows.iter().filter(|m| m.id_ban == id_ban).collect()
+    }
+
+    #[test]
+    fn step3_inside_gets_the_low_precision_penalty() {
+        let parcels = vec![
+            square("A", "01001", 0.0, 0.0, 10.0),
+            square("B", "01001", 100.0, 0.0, 10.0),
+        ];
+        let segment =
+            |id, x| address(id, "01001", x, 5.0).with_position(Some("segment".into()), None);
+        // `edge` is within INSIDE_EPS_M of A but outside its envelope: only Step 3 finds it.
+        let addresses = [segment("edge", 10.005), segment("in", 105.0)];
+        let config = MatchConfig::builder()
+            .low_precision(["segment"], PositionPolicy::Penalize)
+            .build()
+            .unwrap();
+        let rows = run(parcels, &addresses, &config);
+        let inside = |id| {
+            rows_of(&rows, id)
+                .into_iter()
+                .find(|m| m.match_type == MatchType::Inside)
+                .unwrap()
+                .confidence
+        };
+        assert_eq!(inside("in"), 90 - LOW_PRECISION_CONFIDENCE_PENALTY);
+        assert_eq!(inside("edge"), inside("in"));
+    }
+
+    #[test]
+    fn detect_stacked_tags_groups_from_min_size() {
+        let addresses = [
+            address("s1", "01001", 5.0, 5.0),
+            address("s2", "01001", 5.0, 5.0),
+            address("s3", "01001", 5.0, 5.0),
+            address("alone", "01001", 5.0, 5.001),
+        ];
+        let stack = Some(3);
+        assert_eq!(detect_stacked(&addresses, 3), [stack, stack, stack, None]);
+        assert_eq!(detect_stacked(&addresses, 4), [None; 4]);
+        assert_eq!(detect_stacked(&addresses, 0), [None; 4]);
+    }
+
+    /// Stack of two addresses in A (one BAN-linked to A), 15 m from B, 195 m from C.
+    fn stacked_rows(exclude: bool) -> Vec<MatchOutput> {
+        let parcels = vec![
+            square("A", "01001", 0.0, 0.0, 10.0),
+            square("B", "01001", 20.0, 0.0, 10.0),
+            square("C", "01001", 200.0, 0.0, 10.0),
+        ];
+        let addresses = [
+            address("s1", "01001", 5.0, 5.0).with_existing_link("A"),
+            address("s2", "01001", 5.0, 5.0),
+        ];
+        let config = MatchConfig::builder().stacked(2, exclude).build().unwrap();
+        run(parcels, &addresses, &config)
+    }
+
+    #[test]
+    fn stacked_addresses_are_tagged() {
+        let rows = stacked_rows(false);
+        for t in [
+            MatchType::PreExisting,
+            MatchType::Inside,
+            MatchType::BorderNear,
+            MatchType::FallbackNearest,
+        ] {
+            assert!(rows.iter().any(|m| m.match_type == t), "{t:?}");
+        }
+        assert!(rows.iter().all(|m| m.stack_size == Some(2)));
+    }
+
+    #[test]
+    fn exclude_stacked_keeps_only_ban_links() {
+        let rows = stacked_rows(true);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            (rows[0].id_ban.as_str(), &rows[0].match_type),
+            ("s1", &MatchType::PreExisting)
+        );
+        assert_eq!(rows[0].stack_size, Some(2));
+    }
+
+    fn cross_commune(
+        inside: CrossCommunePolicy,
+        border: CrossCommunePolicy,
+        fallback: CrossCommunePolicy,
+        penalty_m: f64,
+    ) -> MatchConfig {
+        MatchConfig::builder()
+            .cross_commune(inside, border, fallback)
+            .cross_commune_penalty_m(penalty_m)
+            .build()
+            .unwrap()
+    }
+
+    /// (match type, parcel, confidence) of the rank-1 rows of `id_ban`.
+    fn links(rows: &[MatchOutput], id_ban: &str) -> Vec<(MatchType, String, u32)> {
+        rows_of(rows, id_ban)
+            .into_iter()
+            .filter(|m| m.rank == 1)
+            .map(|m| {
+                let pid = m.id_parcelle.clone().unwrap_or_default();
+                (m.match_type.clone(), pid, m.confidence)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn step1_inside_cross_commune_policy() {
+        use CrossCommunePolicy::*;
+        let inside = |policy| {
+            let config = cross_commune(policy, Allow, Forbid, 50.0);
+            let parcels = vec![square("X", "01002", 0.0, 0.0, 10.0)];
+            links(
+                &run(parcels, &[address("a", "01001", 5.0, 5.0)], &config),
+                "a",
+            )
+        };
+        let penalized = 90 - CROSS_COMMUNE_CONFIDENCE_PENALTY;
+        assert_eq!(inside(Allow), [(MatchType::Inside, "X".into(), 90)]);
+        assert_eq!(
+            inside(Penalize),
+            [(MatchType::Inside, "X".into(), penalized)]
+        );
+        assert_eq!(inside(Forbid), []);
+    }
+
+    #[test]
+    fn step3_inside_cross_commune_policy() {
+        use CrossCommunePolicy::*;
+        // Within INSIDE_EPS_M of X but outside its envelope: only Step 3 finds it.
+        let inside = |policy| {
+            let config = cross_commune(policy, Allow, Allow, 50.0);
+            let parcels = vec![square("X", "01002", 0.0, 0.0, 10.0)];
+            let rows = run(parcels, &[address("edge", "01001", 10.005, 5.0)], &config);
+            links(&rows, "edge")
+        };
+        let penalized = 90 - CROSS_COMMUNE_CONFIDENCE_PENALTY;
+        assert_eq!(inside(Allow), [(MatchType::Inside, "X".into(), 90)]);
+        assert_eq!(
+            inside(Penalize),
+            [(MatchType::Inside, "X".into(), penalized)]
+        );
+        // Refused as Inside, still the nearest address under the fallback policy.
+        assert_eq!(
+            inside(Forbid),
+            [(MatchType::FallbackNearest, "X".into(), 50)]
+        );
+    }
+
+    #[test]
+    fn step2_border_cross_commune_policy() {
+        use CrossCommunePolicy::*;
+        // b is 5 m from Y (other commune) and 15 m from Z (its commune).
+        let border = |policy, penalty_m| {
+            let config = cross_commune(Allow, policy, Allow, penalty_m);
+            let parcels = vec![
+                square("Y", "01002", 0.0, 0.0, 10.0),
+                square("Z", "01001", 30.0, 0.0, 10.0),
+            ];
+            let rows = run(parcels, &[address("b", "01001", 15.0, 5.0)], &config);
+            rows_of(&rows, "b")
+                .into_iter()
+                .find(|m| m.match_type == MatchType::BorderNear)
+                .map(|m| (m.id_parcelle.clone().unwrap(), m.confidence))
+                .unwrap()
+        };
+        let penalized = 70 - CROSS_COMMUNE_CONFIDENCE_PENALTY;
+        assert_eq!(border(Allow, 50.0), ("Y".into(), 70));
+        // Y still ranks first with a 5 m penalty (10 m < 15 m), not with 50 m.
+        assert_eq!(border(Penalize, 5.0), ("Y".into(), penalized));
+        assert_eq!(border(Penalize, 50.0), ("Z".into(), 70));
+        assert_eq!(border(Forbid, 50.0), ("Z".into(), 70));
+    }
+
+    #[test]
+    fn step3_fallback_cross_commune_policy() {
+        use CrossCommunePolicy::*;
+        // Beyond Step 2: c (other commune) is 90 m from W, d (its commune) 290 m.
+        let fallback = |policy| {
+            let config = cross_commune(Allow, Allow, policy, 50.0);
+            let parcels = vec![square("W", "01002", 0.0, 0.0, 10.0)];
+            let addresses = [
+                address("c", "01001", 100.0, 5.0),
+                address("d", "01002", 300.0, 5.0),
+            ];
+            let rows = run(parcels, &addresses, &config);
+            assert!(rows
+                .iter()
+                .all(|m| m.match_type == MatchType::FallbackNearest));
+            let [m] = rows.as_slice() else {
+                panic!("{rows:?}")
+            };
+            (m.id_ban.clone(), m.confidence)
+        };
+        let penalized = 50 - CROSS_COMMUNE_CONFIDENCE_PENALTY;
+        assert_eq!(fallback(Allow), ("c".into(), 50));
+        assert_eq!(fallback(Penalize), ("c".into(), penalized));
+        assert_eq!(fallback(Forbid), ("d".into(), 50));
+    }
+
+    /// Step 3 rows of parcel W ([0, 10]²), all addresses beyond the Step 2 radius.
+    fn fallback_of_w(addresses: &[AddressInput]) -> MatchOutput {
+        let parcels = vec![square("W", "01001", 0.0, 0.0, 10.0)];
+        let rows = run(parcels, addresses, &MatchConfig::default());
+        let [m] = rows.as_slice() else {
+            panic!("{rows:?}")
+        };
+        assert_eq!(m.match_type, MatchType::FallbackNearest);
+        m.clone()
+    }
+
+    #[test]
+    fn step3_runner_up_margin_is_the_distance_difference() {
+        // c at 90 m, e at 93 m
+        let m = fallback_of_w(&[
+            address("c", "01001", 100.0, 5.0),
+            address("e", "01001", 5.0, 103.0),
+        ]);
+        assert_eq!((m.id_ban.as_str(), m.distance_m), ("c", 90.0));
+        assert_eq!(m.runner_up_id.as_deref(), Some("e"));
+        assert_eq!(m.margin_m, Some(3.0));
+        // 20 * (1 - 3 / 5)
+        assert_eq!(m.confidence, 50 - 8);
+    }
+
+    #[test]
+    fn step3_runner_up_beyond_the_final_radius_is_not_reported() {
+        // The search stops at r = 100 m (90 m + margin cap <= r); g is inside that window's
+        // envelope (corner) but 134 m away.
+        let m = fallback_of_w(&[
+            address("c", "01001", 100.0, 5.0),
+            address("g", "01001", 105.0, 105.0),
+        ]);
+        assert_eq!(m.id_ban, "c");
+        assert_eq!((m.runner_up_id, m.margin_m), (None, None));
+        assert_eq!(m.confidence, 50);
+    }
+
+    #[test]
+    fn step3_closing_pass_matches_a_full_search() {
+        let parcels = || {
+            vec![
+                square("W1", "01001", 0.0, 0.0, 10.0),
+                square("W2", "01001", 500.0, 0.0, 10.0),
+                square("W3", "01001", 0.0, 700.0, 40.0),
+            ]
+        };
+        let addresses: Vec<_> = [
+            (100.0, 5.0),
+            (5.0, 103.0),
+            (105.0, 105.0),
+            (300.0, 300.0),
+            (450.0, 150.0),
+            (620.0, 5.0),
+            (505.0, 97.0),
+            (508.0, 96.0),
+            (70.0, 810.0),
+            (-70.0, 660.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y))| address(&format!("a{i}"), "01001", x, y))
+        .collect();
+        let step3 = |expand_m| {
+            let config = MatchConfig::builder()
+                .fallback_envelope_expand_m(expand_m)
+                .build()
+                .unwrap();
+            let mut rows: Vec<_> = run(parcels(), &addresses, &config)
+                .into_iter()
+                .filter(|m| m.match_type == MatchType::FallbackNearest)
+                .map(|m| {
+                    let key = (m.id_parcelle, m.id_ban, m.runner_up_id);
+                    (key, m.distance_m, m.margin_m, m.confidence)
+                })
+                .collect();
+            rows.sort_by(|a, b| a.0.cmp(&b.0));
+            rows
+        };
+        let full = step3(1500.0);
+        // one runner-up within the margin cap per parcel
+        assert_eq!(full.len(), 3);
+        assert!(full.iter().all(|(key, ..)| key.2.is_some()));
+        assert_eq!(step3(5.0), full);
+    }
 
+    fn run_with_buildings(
+        parcels: Vec<ParcelData>,
+        buildings: Vec<ParcelData>,
+        addresses: &[AddressInput],
+        max_distance_m: f64,
+    ) -> Vec<MatchOutput> {
+        let config = MatchConfig::builder()
+            .prefer_built_parcels(max_distance_m)
+            .build()
+            .unwrap();
+        match_parcels_and_addresses_3_steps(&parcels, addresses, Some(&buildings), &config)
+    }
+
+    /// Empty strip S ([10, 12] x [0, 10]) along Q, whose building B1 is 5 m from the strip.
+    fn strip_rows(x: f64) -> Vec<MatchOutput> {
+        let parcels = vec![
+            square("S", "01001", 10.0, 0.0, 2.0),
+            square("Q", "01001", 12.0, 0.0, 10.0),
+        ];
+        let buildings = vec![square("B1", "01001", 17.0, 3.0, 4.0)];
+        run_with_buildings(parcels, buildings, &[address("c", "01001", x, 5.0)], 10.0)
+    }
+
+    #[test]
+    fn address_in_an_empty_strip_goes_to_the_built_parcel() {
+        // in S (Step 1), then within INSIDE_EPS_M of S outside its envelope (Step 3 only)
+        for x in [11.0, 9.995] {
+            let rows = strip_rows(x);
+            assert!(
+                rows.iter().all(|m| m.match_type != MatchType::Inside),
+                "{rows:?}"
+            );
+            let border = rows
+                .iter()
+                .find(|m| m.match_type == MatchType::BorderNear)
+                .unwrap();
+            assert_eq!(border.id_parcelle.as_deref(), Some("Q"));
+            assert_eq!(border.id_batiment.as_deref(), Some("B1"));
+        }
+    }
+
+    #[test]
+    fn step2_prefers_the_built_parcel_with_a_same_class_runner_up() {
+        // v is 1 m from U (unbuilt), 7 m from Q2 (building 9 m away), 9 m from Q3 (10 m)
+        let v = [address("v", "01001", 5.0, 15.0)];
+        let parcels = || {
+            vec![
+                square("U", "01001", 0.0, 16.0, 10.0),
+                square("Q2", "01001", 0.0, -2.0, 10.0),
+                square("Q3", "01001", 14.0, 10.0, 10.0),
+            ]
+        };
+        let b2 = square("B2", "01001", 3.0, 2.0, 4.0);
+        let b3 = square("B3", "01001", 15.0, 12.0, 3.0);
+        let border = |rows: Vec<MatchOutput>| {
+            rows.into_iter()
+                .find(|m| m.match_type == MatchType::BorderNear)
+                .unwrap()
+        };
+
+        let m = border(run_with_buildings(
+            parcels(),
+            vec![b2.clone(), b3],
+            &v,
+            12.0,
+        ));
+        assert_eq!(m.id_parcelle.as_deref(), Some("Q2"));
+        assert_eq!(m.id_batiment.as_deref(), Some("B2"));
+        assert_eq!(
+            (m.runner_up_id.as_deref(), m.margin_m),
+            (Some("Q3"), Some(2.0))
+        );
+        assert_eq!(m.confidence, 70 - 12);
 
+        // The closer unbuilt parcel is no runner-up: not ambiguous.
+        let m = border(run_with_buildings(parcels(), vec![b2], &v, 12.0));
+        assert_eq!(m.id_parcelle.as_deref(), Some("Q2"));
+        assert_eq!((m.runner_up_id, m.margin_m), (None, None));
+        assert_eq!(m.confidence, 70);
+    }
+}
@@ -0,0 +1,550 @@
+//! Minimal OpenStreetMap PBF reader for address points: `addr:housenumber` nodes and the
+//! centroid of building ways carrying `addr:housenumber`, in CRS84. Raw and zlib blobs, plain and
+//! dense nodes are decoded; relations (multipolygon buildings) are skipped.
+
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use geo::{Centroid, LineString, Polygon};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// PBF format limits (`BlobHeader` 64 KiB, `Blob` 32 MiB).
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+
+/// OSM feature carrying an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmAddress {
+    /// `node/<id>` or `way/<id>`.
+    pub id: String,
+    pub housenumber: String,
+    /// `addr:street`, else `addr:place`.
+    pub street: Option<String>,
+    pub lon: f64,
+    pub lat: f64,
+    /// Building way (centroid) rather than a node.
+    pub building: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Protobuf wire format
+
+enum Wire<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Fields of one protobuf message, in wire order.
+struct Message<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.buf.len() {
+            bail!("truncated protobuf message");
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn field(&mut self) -> Result<(u32, Wire<'a>)> {
+        let key = varint(&mut self.buf)?;
+        let value = match key & 7 {
+            0 => Wire::Varint(varint(&mut self.buf)?),
+            1 => {
+                self.take(8)?;
+                Wire::Fixed
+            }
+            2 => {
+                let len = varint(&mut self.buf)? as usize;
+                Wire::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Wire::Fixed
+            }
+            t => bail!("unsupported protobuf wire type {}", t),
+        };
+        Ok(((key >> 3) as u32, value))
+    }
+}
+
+impl<'a> Iterator for Message<'a> {
+    type Item = Result<(u32, Wire<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        (!self.buf.is_empty()).then(|| self.field())
+    }
+}
+
+fn varint(buf: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf
+            .split_first()
+            .ok_or_else(|| anyhow!("truncated varint"))?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("varint longer than 64 bits")
+}
+
+fn zigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+/// Packed repeated varints (a single unpacked value is accepted too).
+fn packed(value: Wire) -> Result<Vec<u64>> {
+    match value {
+        Wire::Bytes(mut bytes) => {
+            let mut values = Vec::new();
+            while !bytes.is_empty() {
+                values.push(varint(&mut bytes)?);
+            }
+            Ok(values)
+        }
+        Wire::Varint(v) => Ok(vec![v]),
+        Wire::Fixed => bail!("fixed-width value in a varint field"),
+    }
+}
+
+/// Running sum of zigzag deltas (dense node ids / coordinates, way refs).
+fn deltas(values: &[u64]) -> Vec<i64> {
+    values
+        .iter()
+        .scan(0i64, |acc, v| {
+            *acc += zigzag(*v);
+            Some(*acc)
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// OSM blocks
+
+/// Node or way of a primitive block, tags resolved against the block string table.
+enum Element<'b> {
+    Node {
+        id: i64,
+        lon: f64,
+        lat: f64,
+        tags: Vec<(&'b str, &'b str)>,
+    },
+    Way {
+        id: i64,
+        refs: Vec<i64>,
+        tags: Vec<(&'b str, &'b str)>,
+    },
+}
+
+struct Block<'b> {
+    strings: Vec<&'b str>,
+    groups: Vec<&'b [u8]>,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl<'b> Block<'b> {
+    fn parse(data: &'b [u8]) -> Result<Self> {
+        let mut block = Block {
+            strings: Vec::new(),
+            groups: Vec::new(),
+            granularity: 100,
+            lat_offset: 0,
+            lon_offset: 0,
+        };
+        for field in Message::new(data) {
+            match field? {
+                (1, Wire::Bytes(table)) => {
+                    for s in Message::new(table) {
+                        if let (1, Wire::Bytes(s)) = s? {
+                            // Invalid UTF-8 only matters if used as a tag; map it to "".
+                            block
+                                .strings
+                                .push(std::str::from_utf8(s).unwrap_or_default());
+                        }
+                    }
+                }
+                (2, Wire::Bytes(group)) => block.groups.push(group),
+                (17, Wire::Varint(v)) => block.granularity = v as i64,
+                (19, Wire::Varint(v)) => block.lat_offset = v as i64,
+                (20, Wire::Varint(v)) => block.lon_offset = v as i64,
+                _ => {}
+            }
+        }
+        Ok(block)
+    }
+
+    fn string(&self, index: u64) -> Result<&'b str> {
+        self.strings
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("string index {} out of the block table", index))
+    }
+
+    fn tags(&self, keys: &[u64], values: &[u64]) -> Result<Vec<(&'b str, &'b str)>> {
+        keys.iter()
+            .zip(values)
+            .map(|(k, v)| Ok((self.string(*k)?, self.string(*v)?)))
+            .collect()
+    }
+
+    fn degrees(&self, offset: i64, value: i64) -> f64 {
+        1e-9 * (offset + self.granularity * value) as f64
+    }
+
+    fn for_each(&self, mut on_element: impl FnMut(Element<'b>)) -> Result<()> {
+        for group in &self.groups {
+            for field in Message::new(group) {
+                match field? {
+                    (1, Wire::Bytes(node)) => on_element(self.node(node)?),
+                    (2, Wire::Bytes(dense)) => self.dense_nodes(dense, &mut on_element)?,
+                    (3, Wire::Bytes(way)) => on_element(self.way(way)?),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn node(&self, data: &[u8]) -> Result<Element<'b>> {
+        let (mut id, mut lat, mut lon) = (0, 0, 0);
+        let (mut keys, mut values) = (Vec::new(), Vec::new());
+        for field in Message::new(data) {
+            match field? {
+                (1, Wire::Varint(v)) => id = zigzag(v),
+                (2, v) => keys.extend(packed(v)?),
+                (3, v) => values.extend(packed(v)?),
+                (8, Wire::Varint(v)) => lat = zigzag(v),
+                (9, Wire::Varint(v)) => lon = zigzag(v),
+                _ => {}
+            }
+        }
+        Ok(Element::Node {
+            id,
+            lon: self.degrees(self.lon_offset, lon),
+            lat: self.degrees(self.lat_offset, lat),
+            tags: self.tags(&keys, &values)?,
+        })
+    }
+
+    fn dense_nodes(&self, data: &[u8], on_element: &mut impl FnMut(Element<'b>)) -> Result<()> {
+        let (mut ids, mut lats, mut lons, mut keys_vals) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for field in Message::new(data) {
+            match field? {
+                (1, v) => ids.extend(packed(v)?),
+                (8, v) => lats.extend(packed(v)?),
+                (9, v) => lons.extend(packed(v)?),
+                (10, v) => keys_vals.extend(packed(v)?),
+                _ => {}
+            }
+        }
+        if lats.len() != ids.len() || lons.len() != ids.len() {
+            bail!("dense nodes: id / coordinate arrays of different lengths");
+        }
+        // keys_vals: (key, value)* 0 per node, or empty when no node of the block has tags.
+        let mut kv = keys_vals.into_iter();
+        for ((id, lat), lon) in deltas(&ids)
+            .into_iter()
+            .zip(deltas(&lats))
+            .zip(deltas(&lons))
+        {
+            let mut tags = Vec::new();
+            while let Some(key) = kv.next().filter(|k| *k != 0) {
+                let value = kv
+                    .next()
+                    .ok_or_else(|| anyhow!("dense nodes: key without value"))?;
+                tags.push((self.string(key)?, self.string(value)?));
+            }
+            on_element(Element::Node {
+                id,
+                lon: self.degrees(self.lon_offset, lon),
+                lat: self.degrees(self.lat_offset, lat),
+                tags,
+            });
+        }
+        Ok(())
+    }
+
+    fn way(&self, data: &[u8]) -> Result<Element<'b>> {
+        let mut id = 0;
+        let (mut keys, mut values, mut refs) = (Vec::new(), Vec::new(), Vec::new());
+        for field in Message::new(data) {
+            match field? {
+                (1, Wire::Varint(v)) => id = v as i64,
+                (2, v) => keys.extend(packed(v)?),
+                (3, v) => values.extend(packed(v)?),
+                (8, v) => refs.extend(packed(v)?),
+                _ => {}
+            }
+        }
+        Ok(Element::Way {
+            id,
+            refs: deltas(&refs),
+            tags: self.tags(&keys, &values)?,
+        })
+    }
+}
+
+fn blob_data(blob: &[u8]) -> Result<Vec<u8>> {
+    let mut raw_size = 0;
+    for field in Message::new(blob) {
+        match field? {
+            (1, Wire::Bytes(raw)) => return Ok(raw.to_vec()),
+            (2, Wire::Varint(v)) => raw_size = v as usize,
+            (3, Wire::Bytes(zlib)) => {
+                let mut data = Vec::with_capacity(raw_size.min(MAX_BLOB_SIZE));
+                ZlibDecoder::new(zlib)
+                    .take(MAX_BLOB_SIZE as u64)
+                    .read_to_end(&mut data)?;
+                return Ok(data);
+            }
+            (4 | 6 | 7, _) => bail!("unsupported PBF blob compression (lzma / lz4 / zstd)"),
+            _ => {}
+        }
+    }
+    bail!("PBF blob without data")
+}
+
+/// Calls `on_element` for every node and way of the `OSMData` blocks of `path`.
+fn for_each_element(path: &Path, mut on_element: impl FnMut(Element)) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Failed to open OSM PBF {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    loop {
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_HEADER_SIZE {
+            bail!("OSM PBF {:?}: blob header of {} bytes", path, len);
+        }
+        let mut header = vec![0; len];
+        reader.read_exact(&mut header)?;
+        let (mut kind, mut datasize) = (Vec::new(), 0usize);
+        for field in Message::new(&header) {
+            match field? {
+                (1, Wire::Bytes(b)) => kind = b.to_vec(),
+                (3, Wire::Varint(v)) => datasize = v as usize,
+                _ => {}
+            }
+        }
+        if datasize > MAX_BLOB_SIZE {
+            bail!("OSM PBF {:?}: blob of {} bytes", path, datasize);
+        }
+        let mut blob = vec![0; datasize];
+        reader.read_exact(&mut blob)?;
+        if kind == b"OSMData" {
+            let data = blob_data(&blob).with_context(|| format!("OSM PBF {:?}", path))?;
+            Block::parse(&data)?.for_each(&mut on_element)?;
+        }
+    }
+}
+
+fn tag<'t>(tags: &[(&str, &'t str)], key: &str) -> Option<&'t str> {
+    tags.iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| *v)
+        .filter(|v| !v.is_empty())
+}
+
+fn street(tags: &[(&str, &str)]) -> Option<String> {
+    tag(tags, "addr:street")
+        .or_else(|| tag(tags, "addr:place"))
+        .map(str::to_owned)
+}
+
+/// Address nodes and building centroids of an OSM extract. Buildings are read in two passes
+/// (ways, then the coordinates of their nodes only) to keep memory bounded on department
+/// extracts.
+pub fn read_addresses(path: &Path) -> Result<Vec<OsmAddress>> {
+    let mut addresses = Vec::new();
+    // (id, housenumber, street, refs)
+    let mut buildings: Vec<(i64, String, Option<String>, Vec<i64>)> = Vec::new();
+    for_each_element(path, |element| match element {
+        Element::Node { id, lon, lat, tags } => {
+            if let Some(housenumber) = tag(&tags, "addr:housenumber") {
+                addresses.push(OsmAddress {
+                    id: format!("node/{}", id),
+                    housenumber: housenumber.to_string(),
+                    street: street(&tags),
+                    lon,
+                    lat,
+                    building: false,
+                });
+            }
+        }
+        Element::Way { id, refs, tags } => {
+            let is_building = tag(&tags, "building").is_some_and(|b| b != "no");
+            if let (true, Some(housenumber)) = (is_building, tag(&tags, "addr:housenumber")) {
+                buildings.push((id, housenumber.to_string(), street(&tags), refs));
+            }
+        }
+    })?;
+    let nodes = addresses.len();
+
+    if !buildings.is_empty() {
+        let wanted: HashSet<i64> = buildings.iter().flat_map(|b| b.3.iter().copied()).collect();
+        let mut coords: HashMap<i64, (f64, f64)> = HashMap::with_capacity(wanted.len());
+        for_each_element(path, |element| {
+            if let Element::Node { id, lon, lat, .. } = element {
+                if wanted.contains(&id) {
+                    coords.insert(id, (lon, lat));
+                }
+            }
+        })?;
+
+        let mut incomplete = 0usize;
+        for (id, housenumber, street, refs) in buildings {
+            let ring: Option<Vec<(f64, f64)>> =
+                refs.iter().map(|r| coords.get(r).copied()).collect();
+            let Some(centroid) =
+                ring.and_then(|ring| Polygon::new(LineString::from(ring), vec![]).centroid())
+            else {
+                incomplete += 1;
+                continue;
+            };
+            addresses.push(OsmAddress {
+                id: format!("way/{}", id),
+                housenumber,
+                street,
+                lon: centroid.x(),
+                lat: centroid.y(),
+                building: true,
+            });
+        }
+        if incomplete > 0 {
+            warn!(
+                incomplete,
+                "OSM buildings with nodes missing from the extract (skipped)"
+            );
+        }
+    }
+    info!(
+        nodes,
+        buildings = addresses.len() - nodes,
+        "OSM address points read"
+    );
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+        while v >= 0x80 {
+            out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    fn bytes_field(out: &mut Vec<u8>, number: u64, bytes: &[u8]) {
+        put_varint(out, number << 3 | 2);
+        put_varint(out, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+    }
+
+    fn packed_field(out: &mut Vec<u8>, number: u64, values: &[u64]) {
+        let mut buf = Vec::new();
+        values.iter().for_each(|v| put_varint(&mut buf, *v));
+        bytes_field(out, number, &buf);
+    }
+
+    fn zz(v: i64) -> u64 {
+        ((v << 1) ^ (v >> 63)) as u64
+    }
+
+    #[test]
+    fn reads_address_nodes_and_building_centroids() {
+        let strings = ["", "addr:housenumber", "12", "building", "yes", "3"];
+        let mut table = Vec::new();
+        strings
+            .iter()
+            .for_each(|s| bytes_field(&mut table, 1, s.as_bytes()));
+
+        // Nodes 1..=5 (granularity 100 → 1e-7 degrees): node 1 is an address, 2..5 a square.
+        let coords: [(i64, i64); 5] = [
+            (20_000_000, 460_000_000),
+            (20_000_000, 460_000_000),
+            (20_010_000, 460_000_000),
+            (20_010_000, 460_010_000),
+            (20_000_000, 460_010_000),
+        ];
+        let delta = |values: Vec<i64>| -> Vec<u64> {
+            let mut prev = 0;
+            values
+                .into_iter()
+                .map(|v| {
+                    let d = zz(v - prev);
+                    prev = v;
+                    d
+                })
+                .collect()
+        };
+        let mut dense = Vec::new();
+        packed_field(&mut dense, 1, &delta((1..=5).collect()));
+        packed_field(&mut dense, 8, &delta(coords.iter().map(|c| c.1).collect()));
+        packed_field(&mut dense, 9, &delta(coords.iter().map(|c| c.0).collect()));
+        packed_field(&mut dense, 10, &[1, 2, 0, 0, 0, 0, 0]);
+        let mut way = Vec::new();
+        put_varint(&mut way, 1 << 3);
+        put_varint(&mut way, 10);
+        packed_field(&mut way, 2, &[3, 1]);
+        packed_field(&mut way, 3, &[4, 5]);
+        packed_field(&mut way, 8, &delta(vec![2, 3, 4, 5, 2]));
+        let mut group = Vec::new();
+        bytes_field(&mut group, 2, &dense);
+        bytes_field(&mut group, 3, &way);
+        let mut block = Vec::new();
+        bytes_field(&mut block, 1, &table);
+        bytes_field(&mut block, 2, &group);
+
+        let mut blob = Vec::new();
+        bytes_field(&mut blob, 1, &block);
+        let mut header = Vec::new();
+        bytes_field(&mut header, 1, b"OSMData");
+        put_varint(&mut header, 3 << 3);
+        put_varint(&mut header, blob.len() as u64);
+        let mut file = (header.len() as u32).to_be_bytes().to_vec();
+        file.extend(header);
+        file.extend(blob);
+        let path =
+            std::env::temp_dir().join(format!("ban_cadastre_osm_{}.pbf", std::process::id()));
+        std::fs::write(&path, file).unwrap();
+        let addresses = read_addresses(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(addresses.len(), 2);
+        let node = &addresses[0];
+        assert_eq!(
+            (node.id.as_str(), node.housenumber.as_str()),
+            ("node/1", "12")
+        );
+        assert!((node.lon - 2.0).abs() < 1e-9 && (node.lat - 46.0).abs() < 1e-9);
+        let way = &addresses[1];
+        assert_eq!(
+            (way.id.as_str(), way.housenumber.as_str(), way.building),
+            ("way/10", "3", true)
+        );
+        assert!((way.lon - 2.0005).abs() < 1e-9 && (way.lat - 46.0005).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::cli::OsmArgs;
+use anyhow::Result;
+use ban_cadastre::confidence::load_scorer;
+use ban_cadastre::crs::{commune_department, department_crs, LAMBERT_93};
+use ban_cadastre::loader::{load_addresses, load_parcels};
+use ban_cadastre::matcher::match_parcels_and_addresses_3_steps;
+use ban_cadastre::osm::{read_addresses, OsmAddress};
+use ban_cadastre::structures::{AddressInput, MatchConfig, MatchOutput, MatchType, ParcelData};
+use ban_cadastre::writer::MatchWriter;
+use ban_cadastre::Matcher;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::info;
+
+/// BAN / OSM address counts of one parcel (address-centric links only).
+#[derive(Debug, PartialEq, Serialize)]
+struct ConflationRow {
+    id_parcelle: String,
+    code_insee: String,
+    ban_addresses: usize,
+    osm_addresses: usize,
+    /// `both`, `ban_only` or `osm_only` (BAN likely missing an address).
+    status: &'static str,
+}
+
+/// Addresses linked to each parcel by their own position (`PreExisting`, `Inside`,
+/// `BorderNear`, retained candidate); `FallbackNearest` is parcel-centric and not counted.
+fn linked_counts(matches: &[MatchOutput]) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for m in matches {
+        let linked = matches!(
+            m.match_type,
+            MatchType::PreExisting | MatchType::Inside | MatchType::BorderNear
+        );
+        if let (true, 1, Some(pid)) = (linked, m.rank, &m.id_parcelle) {
+            *counts.entry(pid.as_str()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// One row per parcel with at least one BAN or OSM address, in parcel order.
+fn conflate(
+    parcels: &[ParcelData],
+    ban: &[MatchOutput],
+    osm: &[MatchOutput],
+) -> Vec<ConflationRow> {
+    let (ban, osm) = (linked_counts(ban), linked_counts(osm));
+    parcels
+        .iter()
+        .filter_map(|p| {
+            let ban_addresses = ban.get(p.id.as_str()).copied().unwrap_or(0);
+            let osm_addresses = osm.get(p.id.as_str()).copied().unwrap_or(0);
+            let status = match (ban_addresses > 0, osm_addresses > 0) {
+                (true, true) => "both",
+                (true, false) => "ban_only",
+                (false, true) => "osm_only",
+                (false, false) => return None,
+            };
+            Some(ConflationRow {
+                id_parcelle: p.id.clone(),
+                code_insee: p.code_insee.clone(),
+                ban_addresses,
+                osm_addresses,
+                status,
+            })
+        })
+        .collect()
+}
+
+/// OSM points as matcher addresses, in the working CRS. OSM carries no INSEE code: the commune
+/// is the one of the parcel containing the point, else of the nearest parcel within
+/// `max_distance_m`; points farther from every parcel (outside the department) are dropped.
+fn to_addresses(
+    osm: &[OsmAddress],
+    parcels: &Matcher,
+    project: impl Fn(f64, f64) -> (f64, f64),
+    max_distance_m: f64,
+) -> Vec<AddressInput> {
+    osm.iter()
+        .filter_map(|o| {
+            let p = project(o.lon, o.lat).into();
+            let parcel = parcels.parcel_at(&p).or_else(|| {
+                parcels
+                    .nearest_parcel(&p)
+                    .filter(|(_, d)| *d <= max_distance_m)
+                    .map(|(parcel, _)| parcel)
+            })?;
+            let address = AddressInput::new(o.id.clone(), parcel.code_insee.clone(), p).ok()?;
+            // BAN vocabulary, so that `--low-precision-positions` can target building centroids.
+            Some(address.with_position(o.building.then(|| "bâtiment".to_string()), None))
+        })
+        .collect()
+}
+
+pub fn run_osm(args: OsmArgs) -> Result<()> {
+    info!(input=?args.input, output=?args.output, "starting osm mode");
+    let start_load = Instant::now();
+    let parcels = load_parcels(&args.input_parcelles)?;
+    let osm = read_addresses(&args.input)?;
+
+    let dept = args.dept.clone().or_else(|| {
+        parcels
+            .first()
+            .and_then(|p| commune_department(&p.code_insee))
+            .map(str::to_owned)
+    });
+    let crs = dept.as_deref().map(department_crs).unwrap_or(LAMBERT_93);
+    let lookup = Matcher::new(&parcels, &[], MatchConfig::default())?;
+    let addresses = to_addresses(
+        &osm,
+        &lookup,
+        |lon, lat| crs.project(lon, lat),
+        args.distance_threshold,
+    );
+    info!(
+        dept=?dept,
+        crs = crs.code,
+        parcels = parcels.len(),
+        osm_points = osm.len(),
+        outside = osm.len() - addresses.len(),
+        elapsed_ms = start_load.elapsed().as_millis(),
+        "loaded inputs"
+    );
+
+    let mut config = MatchConfig {
+        address_max_distance_m: args.distance_threshold,
+        confidence_scorer: load_scorer(args.confidence_model.as_deref())?,
+        ..MatchConfig::default()
+    };
+    args.cross_commune.apply(&mut config);
+    args.positions.apply(&mut config);
+
+    let start_match = Instant::now();
+    let osm_matches = match_parcels_and_addresses_3_steps(&parcels, &addresses, None, &config);
+    info!(
+        elapsed_ms = start_match.elapsed().as_millis(),
+        matches = osm_matches.len(),
+        "OSM matching completed"
+    );
+
+    if let Some(parent) = args.output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = MatchWriter::new(&args.output, 10_000)?;
+    for m in &osm_matches {
+        writer.write(m.clone())?;
+    }
+    writer.close()?;
+
+    if let (Some(conflation), Some(ban_path)) = (&args.conflation, &args.input_adresses) {
+        let ban_addresses = load_addresses(ban_path)?;
+        let ban_matches =
+            match_parcels_and_addresses_3_steps(&parcels, &ban_addresses, None, &config);
+        let rows = conflate(&parcels, &ban_matches, &osm_matches);
+        let count = |status| rows.iter().filter(|r| r.status == status).count();
+        info!(
+            both = count("both"),
+            ban_only = count("ban_only"),
+            osm_only = count("osm_only"),
+            "BAN / OSM conflation by parcel"
+        );
+        if let Some(parent) = conflation.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut csv = csv::Writer::from_path(conflation)?;
+        for row in &rows {
+            csv.serialize(row)?;
+        }
+        csv.flush()?;
+        info!(artifact=?conflation, "artifact");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::square;
+
+    fn parcels() -> Vec<ParcelData> {
+        vec![
+            square("A", "01001", 0.0, 0.0, 10.0),
+            square("B", "01002", 20.0, 0.0, 10.0),
+        ]
+    }
+
+    fn osm(id: &str, x: f64, building: bool) -> OsmAddress {
+        OsmAddress {
+            id: id.to_string(),
+            housenumber: "1".to_string(),
+            street: None,
+            lon: x,
+            lat: 5.0,
+            building,
+        }
+    }
+
+    fn row(id: &str, pid: &str, match_type: MatchType) -> MatchOutput {
+        MatchOutput::new(id.to_string(), Some(pid.to_string()), 0.0, match_type)
+    }
+
+    #[test]
+    fn osm_points_take_the_commune_of_their_parcel() {
+        let parcels = parcels();
+        let lookup = Matcher::new(&parcels, &[], MatchConfig::default()).unwrap();
+        let points = [osm("node/1", 25.0, false), osm("node/2", 12.0, false)];
+        let addresses = to_addresses(&points, &lookup, |x, y| (x, y), 5.0);
+        let communes: Vec<_> = addresses
+            .iter()
+            .map(|a| (a.id.as_str(), a.code_insee.as_str()))
+            .collect();
+        // node/2 is outside both parcels, 2 m from A.
+        assert_eq!(communes, [("node/1", "01002"), ("node/2", "01001")]);
+    }
+
+    #[test]
+    fn osm_points_far_from_every_parcel_are_dropped() {
+        let parcels = parcels();
+        let lookup = Matcher::new(&parcels, &[], MatchConfig::default()).unwrap();
+        let points = [osm("node/1", 36.0, false), osm("node/3", 500.0, false)];
+        let addresses = to_addresses(&points, &lookup, |x, y| (x, y), 5.0);
+        assert_eq!(addresses.len(), 0);
+        assert_eq!(to_addresses(&points, &lookup, |x, y| (x, y), 10.0).len(), 1);
+    }
+
+    #[test]
+    fn building_centroids_carry_a_building_position() {
+        let parcels = parcels();
+        let lookup = Matcher::new(&parcels, &[], MatchConfig::default()).unwrap();
+        let points = [osm("node/1", 25.0, false), osm("way/2", 5.0, true)];
+        let addresses = to_addresses(&points, &lookup, |x, y| (x, y), 5.0);
+        assert_eq!(addresses[0].type_position, None);
+        assert_eq!(addresses[1].type_position.as_deref(), Some("bâtiment"));
+    }
+
+    #[test]
+    fn conflation_statuses_by_parcel() {
+        let parcels = vec![
+            square("A", "01001", 0.0, 0.0, 10.0),
+            square("B", "01001", 20.0, 0.0, 10.0),
+            square("C", "01001", 40.0, 0.0, 10.0),
+            square("D", "01001", 60.0, 0.0, 10.0),
+        ];
+        let ban = [
+            row("b1", "A", MatchType::Inside),
+            row("b2", "C", MatchType::BorderNear),
+        ];
+        let osm = [
+            row("node/1", "B", MatchType::Inside),
+            row("node/2", "C", MatchType::PreExisting),
+            row("way/3", "D", MatchType::FallbackNearest),
+        ];
+        let rows = conflate(&parcels, &ban, &osm);
+        let status: Vec<_> = rows
+            .iter()
+            .map(|r| (r.id_parcelle.as_str(), r.status))
+            .collect();
+        // D only has a parcel-centric FallbackNearest link: not counted.
+        assert_eq!(
+            status,
+            [("A", "ban_only"), ("B", "osm_only"), ("C", "both")]
+        );
+    }
+
+    #[test]
+    fn conflation_counts_retained_candidates_only() {
+        let parcels = vec![square("A", "01001", 0.0, 0.0, 10.0)];
+        let mut second = row("node/1", "A", MatchType::BorderNear);
+        second.rank = 2;
+        let rows = conflate(&parcels, &[], &[second]);
+        assert!(rows.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::{HashMap, HashSet};
+
+/// Etalab parcel id length: commune (5) + prefixe (3) + section (2) + numéro (4).
+const ID_LEN: usize = 14;
+
+/// Municipalities split into arrondissements: (municipal INSEE code, first and last arrondissement).
+pub const ARRONDISSEMENTS: [(&str, u32, u32); 3] = [
+    ("75056", 75101, 75120), // Paris
+    ("69123", 69381, 69389), // Lyon
+    ("13055", 13201, 13216), // Marseille
+];
+
+/// Syntactic clean-up of a BAN parcel id: trim, upper-case, one-letter section padded
+/// with `0` (`75101000A0012` → `75101000` + `0A` + `0012`). None if it cannot be an Etalab id.
+pub fn normalize_parcel_id(raw: &str) -> Option<String> {
+    let mut id: String = raw
+        .trim()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if id.len() == ID_LEN - 1 && id.is_ascii() {
+        let b = id.as_bytes();
+        if b[8].is_ascii_alphabetic() && b[9..].iter().all(u8::is_ascii_digit) {
+            id.insert(8, '0');
+        }
+    }
+    (id.len() == ID_LEN && id.chars().all(|c| c.is_ascii_alphanumeric())).then_some(id)
+}
+
+/// Other spellings of an Etalab id found in BAN `cad_parcelles`.
+fn id_variants(id: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    if id.len() != ID_LEN || !id.is_ascii() {
+        return out;
+    }
+    let (commune, prefixe, rest) = (&id[..5], &id[5..8], &id[8..]);
+
+    // Merged commune: the absorbed commune keeps its code as prefixe. BAN may use the old
+    // INSEE code with prefixe `000`, or the new code with prefixe `000`.
+    if prefixe != "000" {
+        let old_commune = if commune.starts_with("97") {
+            format!("{}{}", &commune[..3], &prefixe[1..])
+        } else {
+            format!("{}{}", &commune[..2], prefixe)
+        };
+        out.push(format!("{}000{}", old_commune, rest));
+        out.push(format!("{}000{}", commune, rest));
+    }
+
+    // Arrondissements: municipal code with the arrondissement as prefixe, or prefixe `000`.
+    if let Ok(code) = commune.parse::<u32>() {
+        for (municipal, first, last) in ARRONDISSEMENTS {
+            if (first..=last).contains(&code) {
+                out.push(format!("{}{}{}", municipal, &commune[2..], rest));
+                out.push(format!("{}000{}", municipal, rest));
+            }
+        }
+    }
+
+    out
+}
+
+/// How a BAN link was matched to a known parcel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution<'a> {
+    /// The link is a known Etalab id.
+    Exact(&'a str),
+    /// The link is a known variant of this Etalab id.
+    Normalized(&'a str),
+}
+
+impl<'a> Resolution<'a> {
+    pub fn id(&self) -> &'a str {
+        match self {
+            Resolution::Exact(id) | Resolution::Normalized(id) => id,
+        }
+    }
+}
+
+/// Maps BAN `cad_parcelles` ids to the Etalab ids of a parcel set. Variants shared by several
+/// parcels are ambiguous and stay unresolved.
+pub struct ParcelIdResolver<'a> {
+    known: HashSet<&'a str>,
+    /// Variant → Etalab id (None = ambiguous).
+    variants: HashMap<String, Option<&'a str>>,
+}
+
+impl<'a> ParcelIdResolver<'a> {
+    pub fn new(ids: impl IntoIterator<Item = &'a str>) -> Self {
+        let known: HashSet<&'a str> = ids.into_iter().collect();
+        let mut variants: HashMap<String, Option<&'a str>> = HashMap::new();
+        for id in &known {
+            for v in id_variants(id) {
+                variants
+                    .entry(v)
+                    .and_modify(|e| {
+                        if *e != Some(*id) {
+                            *e = None;
+                        }
+                    })
+                    .or_insert(Some(*id));
+            }
+        }
+        Self { known, variants }
+    }
+
+    pub fn resolve(&self, raw: &str) -> Option<Resolution<'a>> {
+        if let Some(id) = self.known.get(raw) {
+            return Some(Resolution::Exact(id));
+        }
+        let id = normalize_parcel_id(raw)?;
+        if let Some(known) = self.known.get(id.as_str()) {
+            return Some(Resolution::Normalized(known));
+        }
+        self.variants
+            .get(&id)
+            .copied()
+            .flatten()
+            .map(Resolution::Normalized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_spelling_and_one_letter_section() {
+        assert_eq!(
+            normalize_parcel_id(" 75101000ab 0012 ").as_deref(),
+            Some("75101000AB0012")
+        );
+        assert_eq!(
+            normalize_parcel_id("75101000A0012").as_deref(),
+            Some("751010000A0012")
+        );
+        assert_eq!(normalize_parcel_id("75101000AB001"), None);
+    }
+
+    #[test]
+    fn resolves_merged_commune_old_code() {
+        let resolver = ParcelIdResolver::new(["01453106AC0001"]);
+        assert_eq!(
+            resolver.resolve("01453106AC0001"),
+            Some(Resolution::Exact("01453106AC0001"))
+        );
+        assert_eq!(
+            resolver.resolve("01106000AC0001"),
+            Some(Resolution::Normalized("01453106AC0001"))
+        );
+    }
+
+    #[test]
+    fn resolves_arrondissement_municipal_code() {
+        let resolver = ParcelIdResolver::new(["75101000AB0012"]);
+        assert_eq!(
+            resolver.resolve("75056101AB0012"),
+            Some(Resolution::Normalized("75101000AB0012"))
+        );
+        assert_eq!(
+            resolver.resolve("75056000AB0012"),
+            Some(Resolution::Normalized("75101000AB0012"))
+        );
+    }
+
+    #[test]
+    fn shared_variant_stays_unresolved() {
+        let resolver = ParcelIdResolver::new(["75101000AB0012", "75102000AB0012"]);
+        assert_eq!(resolver.resolve("75056000AB0012"), None);
+        assert_eq!(
+            resolver.resolve("75056102AB0012"),
+            Some(Resolution::Normalized("75102000AB0012"))
+        );
+    }
+}
@@ -30,6 +30,17 @@ fn list_matching_files(dir: &Path, prefix: &str, suffix: &str) -> Result<Vec<std
     Ok(out)
 }
 
+/// Rows of the departmental `parcelles_adresses_*` files; with the halo a link across a
+/// department border is in both departments' files and is kept once.
+fn distinct_links(glob: &Path) -> String {
+    format!(
+        "SELECT DISTINCT ON (id_ban, id_parcelle, match_type) * \
+         FROM read_parquet('{}', hive_partitioning=false) \
+         ORDER BY id_ban, id_parcelle, match_type, confidence DESC",
+        glob.to_string_lossy().replace("\\", "/")
+    )
+}
+
 pub fn step_aggregate(output_dir: &Path) -> Result<AggregateOutcome> {
     if !output_dir.exists() {
         return Ok(AggregateOutcome {
@@ -57,10 +68,10 @@ pub fn step_aggregate(output_dir: &Path) -> Result<AggregateOutcome> {
         let q_pa = format!(
             r#"
         COPY (
-            SELECT * FROM read_parquet('{}', hive_partitioning=false)
+            {}
         ) TO '{}' (FORMAT 'PARQUET', CODEC 'SNAPPY')
     "#,
-            glob_pa.to_string_lossy().replace("\\", "/"),
+            distinct_links(&glob_pa),
             target_pa.to_string_lossy().replace("\\", "/")
         );
 
@@ -76,10 +87,10 @@ pub fn step_aggregate(output_dir: &Path) -> Result<AggregateOutcome> {
     let q_pa = format!(
         r#"
         COPY (
-            SELECT * FROM read_parquet('{}', hive_partitioning=false)
+            {}
         ) TO '{}' (HEADER, DELIMITER ',')
     "#,
-        glob_pa.to_string_lossy().replace("\\", "/"),
+        distinct_links(&glob_pa),
         target_pa.to_string_lossy().replace("\\", "/")
     );
     conn.execute(&q_pa, [])
@@ -180,6 +191,64 @@ pub fn step_aggregate(output_dir: &Path) -> Result<AggregateOutcome> {
         info!(artifact=?target_worst, "artifact generated");
         generated.push(target_worst);
     }
+    // 5. National Link Agreement
+    // Union qa_link_agreement_*.csv
+    // Schema: agreement, count, pct. Sum count by agreement, recalc pct.
+    let agreement_inputs = list_matching_files(output_dir, "qa_link_agreement_", ".csv")?;
+    if agreement_inputs.is_empty() {
+        warn!(output_dir=?output_dir, "aggregate: missing inputs qa_link_agreement_*.csv (skipping national_qa_link_agreement.csv)");
+        missing_inputs.push("qa_link_agreement_*.csv".into());
+    } else {
+        let glob_agreement = output_dir.join("qa_link_agreement_*.csv");
+        let target_agreement = output_dir.join("national_qa_link_agreement.csv");
+
+        let q_agreement = format!(
+            r#"
+        COPY (
+            SELECT
+                agreement,
+                SUM(CAST(count AS BIGINT)) AS count,
+                (SUM(CAST(count AS BIGINT))::DOUBLE / SUM(SUM(CAST(count AS BIGINT))) OVER () * 100.0) AS pct
+            FROM read_csv('{}', header=true, auto_detect=true)
+            GROUP BY agreement
+            ORDER BY SUM(CAST(count AS BIGINT)) DESC
+        ) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            glob_agreement.to_string_lossy().replace("\\", "/"),
+            target_agreement.to_string_lossy().replace("\\", "/"),
+        );
+        conn.execute(&q_agreement, [])
+            .context("Aggregate national_qa_link_agreement.csv")?;
+        info!(artifact=?target_agreement, "artifact generated");
+        generated.push(target_agreement);
+    }
+    // 6. National DVF agreement (only when the pipeline ran with --dvf-dir)
+    // Union qa_dvf_agreement_*.csv, same schema and recalculation as link agreement.
+    let dvf_inputs = list_matching_files(output_dir, "qa_dvf_agreement_", ".csv")?;
+    if !dvf_inputs.is_empty() {
+        let glob_dvf = output_dir.join("qa_dvf_agreement_*.csv");
+        let target_dvf = output_dir.join("national_qa_dvf_agreement.csv");
+
+        let q_dvf = format!(
+            r#"
+        COPY (
+            SELECT
+                agreement,
+                SUM(CAST(count AS BIGINT)) AS count,
+                (SUM(CAST(count AS BIGINT))::DOUBLE / NULLIF(SUM(SUM(CAST(count AS BIGINT))) OVER (), 0) * 100.0) AS pct
+            FROM read_csv('{}', header=true, auto_detect=true)
+            GROUP BY agreement
+            ORDER BY SUM(CAST(count AS BIGINT)) DESC
+        ) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            glob_dvf.to_string_lossy().replace("\\", "/"),
+            target_dvf.to_string_lossy().replace("\\", "/"),
+        );
+        conn.execute(&q_dvf, [])
+            .context("Aggregate national_qa_dvf_agreement.csv")?;
+        info!(artifact=?target_dvf, "artifact generated");
+        generated.push(target_dvf);
+    }
     let partial = !missing_inputs.is_empty();
     Ok(AggregateOutcome {
         generated,
@@ -48,6 +48,29 @@ pub fn step_download(dept: &str, raw_dir: &Path, force: bool) -> Result<(PathBuf
     Ok((adresses_csv, parcelles_json))
 }
 
+/// Cadastre `batiments` layer (same Etalab export as the parcels), for building-aware matching.
+pub fn step_download_buildings(dept: &str, raw_dir: &Path, force: bool) -> Result<PathBuf> {
+    let batiments_gz = raw_dir.join(format!("cadastre-{}-batiments.json.gz", dept));
+    let batiments_json = raw_dir.join(format!("cadastre-{}-batiments.json", dept));
+
+    if !raw_dir.exists() {
+        std::fs::create_dir_all(raw_dir)?;
+    }
+
+    let url = format!("https://cadastre.data.gouv.fr/data/etalab-cadastre/latest/geojson/departements/{}/cadastre-{}-batiments.json.gz", dept, dept);
+
+    if force || !batiments_json.exists() {
+        info!(dept=%dept, "downloading buildings");
+        download_file(&url, &batiments_gz)?;
+        info!(dept=%dept, "decompressing buildings");
+        gunzip_file(&batiments_gz, &batiments_json)?;
+    } else {
+        info!(dept=%dept, "buildings already exist; skipping download");
+    }
+
+    Ok(batiments_json)
+}
+
 fn download_file(url: &str, target: &Path) -> Result<()> {
     // Retry logic could be added here
     let client = Client::builder()
@@ -0,0 +1,390 @@
+//! DVF (demandes de valeurs foncières, géo-DVF Etalab layout) as an external check of the
+//! matcher: each DVF mutation parcel is compared with the parcels matched to the BAN address of
+//! the transaction.
+
+use crate::prepare::native::open_input;
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{Array, StringArray, UInt32Array};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use ban_cadastre::crs::WorkingCrs;
+use ban_cadastre::loader::{load_addresses, load_parcels};
+use ban_cadastre::parcel_id::ParcelIdResolver;
+use ban_cadastre::structures::MatchConfig;
+use ban_cadastre::Matcher;
+use geo::Point;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Agreement values, in report order.
+const AGREEMENTS: [&str; 5] = [
+    "agree",
+    "disagree",
+    "unmatched",
+    "no_address",
+    "unknown_parcel",
+];
+
+/// One DVF (mutation, parcel) pair.
+#[derive(Debug, Clone, PartialEq)]
+struct DvfParcel {
+    id_mutation: String,
+    id_parcelle: String,
+    /// BAN interoperability key rebuilt from the DVF address (`<insee>_<voie>_<numero>[_<suffixe>]`).
+    ban_key: Option<String>,
+    lon_lat: Option<(f64, f64)>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct DvfComparison {
+    id_mutation: String,
+    id_parcelle: String,
+    id_ban: Option<String>,
+    /// `key` (DVF address = BAN id) or `nearest` (nearest BAN address to the DVF point).
+    lookup: Option<&'static str>,
+    address_distance_m: Option<f64>,
+    /// Parcels matched to the BAN address, `|`-separated.
+    matched_parcelles: String,
+    agreement: &'static str,
+}
+
+/// geo-DVF file of a department in `dir`: `<DEP>.csv.gz` or `<DEP>.csv`.
+pub fn department_file(dir: &Path, dept: &str) -> Option<PathBuf> {
+    [format!("{}.csv.gz", dept), format!("{}.csv", dept)]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|p| p.exists())
+}
+
+/// DVF suffix (`B`, `T`, `Q` or a letter) as written in BAN ids.
+fn ban_suffix(suffix: &str) -> String {
+    match suffix.trim().to_ascii_uppercase().as_str() {
+        "B" => "bis".to_string(),
+        "T" => "ter".to_string(),
+        "Q" => "quater".to_string(),
+        s => s.to_ascii_lowercase(),
+    }
+}
+
+fn ban_key(commune: &str, voie: &str, numero: &str, suffix: &str) -> Option<String> {
+    let numero: u32 = numero.trim().parse().ok()?;
+    let (commune, voie) = (commune.trim(), voie.trim().to_ascii_lowercase());
+    if commune.is_empty() || voie.is_empty() {
+        return None;
+    }
+    let mut key = format!("{}_{}_{:05}", commune, voie, numero);
+    if !suffix.trim().is_empty() {
+        key.push('_');
+        key.push_str(&ban_suffix(suffix));
+    }
+    Some(key)
+}
+
+/// Distinct (mutation, parcel) pairs of a géo-DVF CSV (plain or `.gz`).
+fn read_dvf(path: &Path) -> Result<Vec<DvfParcel>> {
+    let mut csv = csv::Reader::from_reader(open_input(path)?);
+    let headers = csv.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim() == name);
+    let required =
+        |name: &str| column(name).ok_or_else(|| anyhow!("DVF {:?}: missing column {}", path, name));
+    let (mutation_col, parcel_col) = (required("id_mutation")?, required("id_parcelle")?);
+    let (commune_col, voie_col, numero_col, suffix_col) = (
+        column("code_commune"),
+        column("adresse_code_voie"),
+        column("adresse_numero"),
+        column("adresse_suffixe"),
+    );
+    let (lon_col, lat_col) = (column("longitude"), column("latitude"));
+
+    let mut seen = HashSet::new();
+    let mut rows = Vec::new();
+    for record in csv.records() {
+        let record = record.with_context(|| format!("DVF {:?}", path))?;
+        let field = |col: Option<usize>| col.and_then(|c| record.get(c)).unwrap_or_default();
+        let (id_mutation, id_parcelle) = (field(Some(mutation_col)), field(Some(parcel_col)));
+        if id_parcelle.is_empty()
+            || !seen.insert((id_mutation.to_string(), id_parcelle.to_string()))
+        {
+            continue;
+        }
+        let number = |col| field(col).trim().parse::<f64>().ok();
+        rows.push(DvfParcel {
+            id_mutation: id_mutation.to_string(),
+            id_parcelle: id_parcelle.to_string(),
+            ban_key: ban_key(
+                field(commune_col),
+                field(voie_col),
+                field(numero_col),
+                field(suffix_col),
+            ),
+            lon_lat: number(lon_col).zip(number(lat_col)),
+        });
+    }
+    Ok(rows)
+}
+
+/// Parcels of the retained links (`rank = 1`, `None` / `PreExistingFar` excluded) per address.
+fn load_links(matches_path: &Path) -> Result<HashMap<String, Vec<String>>> {
+    let file = File::open(matches_path)
+        .with_context(|| format!("Failed to open matches file: {:?}", matches_path))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .context("Failed to create parquet reader")?
+        .build()?;
+    let mut links: HashMap<String, Vec<String>> = HashMap::new();
+    for batch in reader {
+        let batch = batch?;
+        let text = |name: &str| -> Result<StringArray> {
+            let col = batch
+                .column_by_name(name)
+                .ok_or_else(|| anyhow!("Matches {:?}: missing column {}", matches_path, name))?;
+            Ok(cast(col, &DataType::Utf8)?
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone())
+        };
+        let (id_ban, id_parcelle, match_type) =
+            (text("id_ban")?, text("id_parcelle")?, text("match_type")?);
+        // `rank` is absent from files written before candidate mode: all rows are retained.
+        let rank = match batch.column_by_name("rank") {
+            Some(col) => Some(
+                cast(col, &DataType::UInt32)?
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .unwrap()
+                    .clone(),
+            ),
+            None => None,
+        };
+        for i in 0..batch.num_rows() {
+            let retained = rank
+                .as_ref()
+                .is_none_or(|r| r.is_null(i) || r.value(i) == 1);
+            let linked = !matches!(match_type.value(i), "None" | "PreExistingFar");
+            if retained && linked && id_parcelle.is_valid(i) {
+                links
+                    .entry(id_ban.value(i).to_string())
+                    .or_default()
+                    .push(id_parcelle.value(i).to_string());
+            }
+        }
+    }
+    Ok(links)
+}
+
+fn compare(
+    dvf: Vec<DvfParcel>,
+    session: &Matcher,
+    resolver: &ParcelIdResolver,
+    links: &HashMap<String, Vec<String>>,
+    crs: &WorkingCrs,
+    max_distance_m: f64,
+) -> Vec<DvfComparison> {
+    dvf.into_iter()
+        .map(|row| {
+            let address = row
+                .ban_key
+                .as_deref()
+                .and_then(|key| session.address(key))
+                .map(|a| (a, "key", None))
+                .or_else(|| {
+                    let (lon, lat) = row.lon_lat?;
+                    let p: Point<f64> = crs.project(lon, lat).into();
+                    session
+                        .nearest_address(&p)
+                        .filter(|(_, d)| *d <= max_distance_m)
+                        .map(|(a, d)| (a, "nearest", Some(d)))
+                });
+            let matched = address
+                .and_then(|(a, _, _)| links.get(&a.id))
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let agreement = match (resolver.resolve(&row.id_parcelle), address) {
+                (None, _) => "unknown_parcel",
+                (Some(_), None) => "no_address",
+                (Some(_), Some(_)) if matched.is_empty() => "unmatched",
+                (Some(r), Some(_)) if matched.iter().any(|m| m == r.id()) => "agree",
+                _ => "disagree",
+            };
+            DvfComparison {
+                id_mutation: row.id_mutation,
+                id_parcelle: row.id_parcelle,
+                id_ban: address.map(|(a, _, _)| a.id.clone()),
+                lookup: address.map(|(_, lookup, _)| lookup),
+                address_distance_m: address.and_then(|(_, _, d)| d),
+                matched_parcelles: matched.join("|"),
+                agreement,
+            }
+        })
+        .collect()
+}
+
+/// Writes `qa_dvf_agreement_<DEP>.csv` (agreement, count, pct) and
+/// `qa_dvf_disagreements_<DEP>.csv`; returns agree / (agree + disagree) in percent.
+pub fn step_dvf(
+    dept: &str,
+    dvf_path: &Path,
+    staging_dir: &Path,
+    results_dir: &Path,
+    output_dir: &Path,
+    crs: &WorkingCrs,
+    max_distance_m: f64,
+) -> Result<f64> {
+    let parcels = load_parcels(&staging_dir.join(format!("parcelles_{}.parquet", dept)))?;
+    let addresses = load_addresses(&staging_dir.join(format!("adresses_{}.parquet", dept)))?;
+    let links = load_links(&results_dir.join(format!("matches_{}.parquet", dept)))?;
+    let dvf = read_dvf(dvf_path)?;
+    let session = Matcher::new(&parcels, &addresses, MatchConfig::default())?;
+    let resolver = ParcelIdResolver::new(parcels.iter().map(|p| p.id.as_str()));
+    let rows = compare(dvf, &session, &resolver, &links, crs, max_distance_m);
+
+    let count = |agreement: &str| rows.iter().filter(|r| r.agreement == agreement).count();
+    let agreement_csv = output_dir.join(format!("qa_dvf_agreement_{}.csv", dept));
+    let mut csv = csv::Writer::from_path(&agreement_csv)?;
+    csv.write_record(["agreement", "count", "pct"])?;
+    for agreement in AGREEMENTS {
+        let pct = count(agreement) as f64 / rows.len().max(1) as f64 * 100.0;
+        csv.write_record([agreement, &count(agreement).to_string(), &pct.to_string()])?;
+    }
+    csv.flush()?;
+
+    let disagreements_csv = output_dir.join(format!("qa_dvf_disagreements_{}.csv", dept));
+    let mut csv = csv::Writer::from_path(&disagreements_csv)?;
+    for row in rows.iter().filter(|r| r.agreement == "disagree") {
+        csv.serialize(row)?;
+    }
+    csv.flush()?;
+
+    let (agree, disagree) = (count("agree"), count("disagree"));
+    let agreement_pct = if agree + disagree > 0 {
+        agree as f64 / (agree + disagree) as f64 * 100.0
+    } else {
+        0.0
+    };
+    info!(
+        dept=%dept,
+        dvf_parcels = rows.len(),
+        agree,
+        disagree,
+        unmatched = count("unmatched"),
+        no_address = count("no_address"),
+        unknown_parcel = count("unknown_parcel"),
+        agreement_pct,
+        "DVF comparison"
+    );
+    Ok(agreement_pct)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{address, square, LAMBERT_93_ORIGIN};
+    use ban_cadastre::crs::LAMBERT_93;
+    use ban_cadastre::structures::{AddressInput, ParcelData};
+
+    const P1: &str = "01001000AB0001";
+    const P2: &str = "01001000AB0002";
+    const A7: &str = "01001_0012_00007";
+    const A9: &str = "01001_0012_00009";
+
+    /// Two 10 m parcels east of the Lambert-93 origin, one address in each; only A7 is matched
+    /// (to P1).
+    fn fixture() -> (
+        Vec<ParcelData>,
+        Vec<AddressInput>,
+        HashMap<String, Vec<String>>,
+    ) {
+        let (x0, y0) = LAMBERT_93_ORIGIN;
+        let parcels = vec![
+            square(P1, "01001", x0, y0, 10.0),
+            square(P2, "01001", x0 + 20.0, y0, 10.0),
+        ];
+        let addresses = vec![
+            address(A7, "01001", x0 + 5.0, y0 + 5.0),
+            address(A9, "01001", x0 + 25.0, y0 + 5.0),
+        ];
+        let links = HashMap::from([(A7.to_string(), vec![P1.to_string()])]);
+        (parcels, addresses, links)
+    }
+
+    fn dvf(parcel: &str, key: Option<&str>, lon_lat: Option<(f64, f64)>) -> DvfParcel {
+        DvfParcel {
+            id_mutation: "2024-1".to_string(),
+            id_parcelle: parcel.to_string(),
+            ban_key: key.map(str::to_owned),
+            lon_lat,
+        }
+    }
+
+    fn compare_one(row: DvfParcel) -> DvfComparison {
+        let (parcels, addresses, links) = fixture();
+        let session = Matcher::new(&parcels, &addresses, MatchConfig::default()).unwrap();
+        let resolver = ParcelIdResolver::new(parcels.iter().map(|p| p.id.as_str()));
+        let mut rows = compare(vec![row], &session, &resolver, &links, &LAMBERT_93, 50.0);
+        rows.pop().unwrap()
+    }
+
+    #[test]
+    fn ban_key_is_rebuilt_from_the_dvf_address() {
+        assert_eq!(
+            ban_key("01001", "B012", "7", "B").as_deref(),
+            Some("01001_b012_00007_bis")
+        );
+        assert_eq!(
+            ban_key("01001", "0012", "12", "").as_deref(),
+            Some("01001_0012_00012")
+        );
+        assert_eq!(ban_key("01001", "0012", "", ""), None);
+        assert_eq!(ban_key("", "0012", "1", ""), None);
+    }
+
+    #[test]
+    fn address_found_by_key_agrees_or_disagrees() {
+        let agree = compare_one(dvf(P1, Some(A7), None));
+        assert_eq!((agree.agreement, agree.lookup), ("agree", Some("key")));
+        assert_eq!(agree.matched_parcelles, P1);
+        let disagree = compare_one(dvf(P2, Some(A7), None));
+        assert_eq!(disagree.agreement, "disagree");
+    }
+
+    #[test]
+    fn address_falls_back_to_the_nearest_ban_point() {
+        // The Lambert-93 origin (3°E, 46.5°N) is 7 m from A7.
+        let row = compare_one(dvf(P1, None, Some((3.0, 46.5))));
+        assert_eq!((row.agreement, row.lookup), ("agree", Some("nearest")));
+        assert_eq!(row.id_ban.as_deref(), Some(A7));
+        assert!((row.address_distance_m.unwrap() - 50f64.sqrt()).abs() < 1e-6);
+        // An unknown key falls back too.
+        let row = compare_one(dvf(P1, Some("01001_9999_00001"), Some((3.0, 46.5))));
+        assert_eq!(row.lookup, Some("nearest"));
+    }
+
+    #[test]
+    fn address_without_retained_link_is_unmatched() {
+        let row = compare_one(dvf(P2, Some(A9), None));
+        assert_eq!(
+            (row.agreement, row.matched_parcelles.as_str()),
+            ("unmatched", "")
+        );
+    }
+
+    #[test]
+    fn no_key_and_no_nearby_point_is_no_address() {
+        assert_eq!(compare_one(dvf(P2, None, None)).agreement, "no_address");
+        // 3.01°E is about 770 m east of the origin: beyond max_distance_m.
+        let far = compare_one(dvf(P2, None, Some((3.01, 46.5))));
+        assert_eq!((far.agreement, far.id_ban), ("no_address", None));
+    }
+
+    #[test]
+    fn parcel_outside_the_cadastre_is_unknown_parcel() {
+        let row = compare_one(dvf("01001000ZZ0001", Some(A7), None));
+        assert_eq!(row.agreement, "unknown_parcel");
+        // The address is still reported.
+        assert_eq!(row.id_ban.as_deref(), Some(A7));
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use ban_cadastre::crs::{commune_department, department_crs};
+use ban_cadastre::loader::load_parcels;
+use ban_cadastre::matcher::preexisting_output;
+use ban_cadastre::parcel_id::{normalize_parcel_id, ParcelIdResolver};
+use ban_cadastre::structures::{AddressInput, MatchOutput, ParcelData};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Resolves BAN links left unresolved by the matcher against the staged parcels of the
+/// department each parcel id belongs to. Returns the PreExisting / PreExistingFar rows of
+/// the resolved links and the number of links still unresolved.
+pub fn resolve_foreign_links(
+    dept: &str,
+    staging_dir: &Path,
+    links: &[(&AddressInput, &str)],
+    max_distance_m: f64,
+) -> Result<(Vec<MatchOutput>, usize)> {
+    let mut by_dept: BTreeMap<String, Vec<(&AddressInput, &str)>> = BTreeMap::new();
+    let mut unresolved = 0usize;
+    for &(addr, pid) in links {
+        // Etalab parcel ids start with the commune code.
+        let other =
+            normalize_parcel_id(pid).and_then(|id| commune_department(&id).map(str::to_owned));
+        match other {
+            // A link to an own parcel missing from staging cannot be resolved elsewhere.
+            Some(other) if other != dept => by_dept.entry(other).or_default().push((addr, pid)),
+            _ => unresolved += 1,
+        }
+    }
+
+    let mut out = Vec::new();
+    for (other, links) in by_dept {
+        if department_crs(&other) != department_crs(dept) {
+            info!(dept=%dept, foreign_dept=%other, links=links.len(), "foreign links: department in another CRS");
+            unresolved += links.len();
+            continue;
+        }
+        let parcels_path = staging_dir.join(format!("parcelles_{}.parquet", other));
+        if !parcels_path.exists() {
+            info!(dept=%dept, foreign_dept=%other, links=links.len(), "foreign links: department not staged");
+            unresolved += links.len();
+            continue;
+        }
+        let parcels = match load_parcels(&parcels_path) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(dept=%dept, foreign_dept=%other, error=%e, "foreign links: cannot read parcels");
+                unresolved += links.len();
+                continue;
+            }
+        };
+        let known: HashMap<&str, &ParcelData> =
+            parcels.iter().map(|p| (p.id.as_str(), p)).collect();
+        let resolver = ParcelIdResolver::new(known.keys().copied());
+
+        let n = out.len();
+        for (addr, pid) in &links {
+            match resolver.resolve(pid).and_then(|r| known.get(r.id())) {
+                Some(parcel) => out.push(preexisting_output(addr, pid, parcel, max_distance_m)),
+                None => unresolved += 1,
+            }
+        }
+        info!(
+            dept=%dept,
+            foreign_dept=%other,
+            links=links.len(),
+            resolved=out.len() - n,
+            "foreign links resolved"
+        );
+    }
+
+    Ok((out, unresolved))
+}
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use ban_cadastre::crs::department_crs;
+use ban_cadastre::indexer::DepartmentIndex;
+use ban_cadastre::loader::{load_addresses, load_buildings, load_parcels};
+use ban_cadastre::structures::{AddressInput, ParcelData};
+use rstar::{Envelope, AABB};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Bounding box of a department's staged parcels, cached as `extent_<DEP>.json` in staging.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Extent {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Extent {
+    fn of(parcels: &[ParcelData]) -> Option<Self> {
+        let mut it = parcels.iter().map(|p| p.envelope);
+        let first = it.next()?;
+        Some(Self::from_aabb(&it.fold(first, |acc, e| acc.merged(&e))))
+    }
+
+    fn from_aabb(env: &AABB<[f64; 2]>) -> Self {
+        Self {
+            min_x: env.lower()[0],
+            min_y: env.lower()[1],
+            max_x: env.upper()[0],
+            max_y: env.upper()[1],
+        }
+    }
+
+    fn intersects(&self, other: &Extent, margin: f64) -> bool {
+        self.min_x - margin <= other.max_x
+            && other.min_x <= self.max_x + margin
+            && self.min_y - margin <= other.max_y
+            && other.min_y <= self.max_y + margin
+    }
+}
+
+/// Departments with staged parcels and addresses (`parcelles_<DEP>.parquet` + `adresses_<DEP>.parquet`).
+fn staged_departments(staging_dir: &Path) -> Result<Vec<String>> {
+    let mut depts = Vec::new();
+    for entry in std::fs::read_dir(staging_dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if let Some(dept) = name
+            .strip_prefix("parcelles_")
+            .and_then(|s| s.strip_suffix(".parquet"))
+        {
+            if staging_dir
+                .join(format!("adresses_{}.parquet", dept))
+                .exists()
+            {
+                depts.push(dept.to_string());
+            }
+        }
+    }
+    depts.sort();
+    Ok(depts)
+}
+
+/// Cached extent, recomputed when the parcels file is newer than the cache.
+fn department_extent(staging_dir: &Path, dept: &str) -> Result<Option<Extent>> {
+    let parcels_path = staging_dir.join(format!("parcelles_{}.parquet", dept));
+    let extent_path = staging_dir.join(format!("extent_{}.json", dept));
+
+    let cache_fresh = match (
+        std::fs::metadata(&extent_path).and_then(|m| m.modified()),
+        std::fs::metadata(&parcels_path).and_then(|m| m.modified()),
+    ) {
+        (Ok(cached), Ok(source)) => cached >= source,
+        _ => false,
+    };
+    if cache_fresh {
+        let file = File::open(&extent_path).context("Failed to open extent cache")?;
+        if let Ok(extent) = serde_json::from_reader(BufReader::new(file)) {
+            return Ok(Some(extent));
+        }
+    }
+
+    let Some(extent) = Extent::of(&load_parcels(&parcels_path)?) else {
+        return Ok(None);
+    };
+    let file = File::create(&extent_path).context("Failed to create extent cache")?;
+    serde_json::to_writer(BufWriter::new(file), &extent).context("Failed to write extent cache")?;
+    Ok(Some(extent))
+}
+
+/// Objects of neighbouring departments taking part in the matching of a department.
+#[derive(Default)]
+pub struct Halo {
+    pub parcels: Vec<ParcelData>,
+    pub addresses: Vec<AddressInput>,
+    /// Empty unless asked for (`prefer_built_parcels`).
+    pub buildings: Vec<ParcelData>,
+}
+
+/// Parcels, addresses and (with `with_buildings`) buildings of neighbouring staged departments
+/// whose envelope comes within `halo_m` of an own parcel envelope.
+pub fn load_halo(
+    dept: &str,
+    staging_dir: &Path,
+    own_index: &DepartmentIndex,
+    halo_m: f64,
+    with_buildings: bool,
+) -> Result<Halo> {
+    let mut halo = Halo::default();
+
+    if own_index.tree.size() == 0 {
+        return Ok(halo);
+    }
+    let own_extent = Extent::from_aabb(&own_index.tree.root().envelope());
+    // Cache our own extent for the neighbours' runs.
+    department_extent(staging_dir, dept)?;
+
+    let near_own = |env: &AABB<[f64; 2]>| {
+        let lo = env.lower();
+        let hi = env.upper();
+        let expanded = AABB::from_corners(
+            [lo[0] - halo_m, lo[1] - halo_m],
+            [hi[0] + halo_m, hi[1] + halo_m],
+        );
+        own_index
+            .tree
+            .locate_in_envelope_intersecting(&expanded)
+            .next()
+            .is_some()
+    };
+
+    for other in staged_departments(staging_dir)? {
+        // Coordinates of departments prepared in another CRS are not comparable.
+        if other == dept || department_crs(&other) != department_crs(dept) {
+            continue;
+        }
+        let extent = match department_extent(staging_dir, &other) {
+            Ok(Some(e)) => e,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!(dept=%dept, neighbour=%other, error=%e, "halo: cannot read neighbour extent; skipped");
+                continue;
+            }
+        };
+        if !own_extent.intersects(&extent, halo_m) {
+            continue;
+        }
+
+        let parcels = load_parcels(&staging_dir.join(format!("parcelles_{}.parquet", other)))?;
+        let addresses = load_addresses(&staging_dir.join(format!("adresses_{}.parquet", other)))?;
+        let n_parcels = halo.parcels.len();
+        let n_addresses = halo.addresses.len();
+        let n_buildings = halo.buildings.len();
+        halo.parcels
+            .extend(parcels.into_iter().filter(|p| near_own(&p.envelope)));
+        halo.addresses.extend(
+            addresses
+                .into_iter()
+                .filter(|a| near_own(&AABB::from_point([a.geom.x(), a.geom.y()]))),
+        );
+        if with_buildings {
+            let buildings_path = staging_dir.join(format!("batiments_{}.parquet", other));
+            if buildings_path.exists() {
+                halo.buildings.extend(
+                    load_buildings(&buildings_path)?
+                        .into_iter()
+                        .filter(|b| near_own(&b.envelope)),
+                );
+            } else {
+                warn!(dept=%dept, neighbour=%other, "halo: neighbour staged without buildings; its parcels look unbuilt");
+            }
+        }
+        info!(
+            dept=%dept,
+            neighbour=%other,
+            parcels=halo.parcels.len() - n_parcels,
+            addresses=halo.addresses.len() - n_addresses,
+            buildings=halo.buildings.len() - n_buildings,
+            "halo neighbour loaded"
+        );
+    }
+
+    Ok(halo)
+}
@@ -1,20 +1,38 @@
-use crate::loader::{load_addresses, load_parcels};
-use crate::matcher::match_parcels_and_addresses_3_steps;
-use crate::structures::MatchConfig;
-use crate::writer::MatchWriter;
+use crate::pipeline::foreign_links::resolve_foreign_links;
+use crate::pipeline::halo::load_halo;
 use anyhow::Result;
+use ban_cadastre::cog::CogHistory;
+use ban_cadastre::indexer::DepartmentIndex;
+use ban_cadastre::loader::{
+    load_addresses, load_buildings, load_parcels, normalize_address_communes,
+    normalize_parcel_communes,
+};
+use ban_cadastre::matcher::{match_parcels_and_addresses_3_steps, unresolved_links};
+use ban_cadastre::parcel_id::ParcelIdResolver;
+use ban_cadastre::structures::{MatchConfig, ParcelStore};
+use ban_cadastre::writer::MatchWriter;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 use tracing::{info, warn};
 
+/// Which inputs a department match reads, besides its own staging files.
+pub struct MatchScope<'a> {
+    pub filter_commune: Option<&'a String>,
+    pub limit_addresses: Option<usize>,
+    /// Cross-department halo (meters), see `halo::load_halo`.
+    pub halo_m: Option<f64>,
+    /// `code_insee` of all inputs (and `filter_commune`) normalized to the COG reference year.
+    pub cog: Option<&'a CogHistory>,
+}
+
 pub fn step_match(
     dept: &str,
     staging_dir: &Path,
     results_dir: &Path,
     config: &MatchConfig,
     quick_qa: bool,
-    filter_commune: Option<&String>,
-    limit_addresses: Option<usize>,
+    scope: &MatchScope,
 ) -> Result<PathBuf> {
     let output_path = results_dir.join(format!("matches_{}.parquet", dept));
 
@@ -37,20 +55,42 @@ pub fn step_match(
     let t_load = Instant::now();
     let mut parcels = load_parcels(&parcels_path)?;
     let mut addresses = load_addresses(&addresses_path)?;
+    let mut buildings = if config.prefer_built_parcels {
+        let buildings_path = staging_dir.join(format!("batiments_{}.parquet", dept));
+        Some(load_buildings(&buildings_path)?)
+    } else {
+        None
+    };
     info!(
         dept=%dept,
         parcels=parcels.len(),
         addresses=addresses.len(),
+        buildings=buildings.as_ref().map(Vec::len),
         duration_s=t_load.elapsed().as_secs_f32(),
         "loaded match inputs"
     );
+    if let Some(cog) = scope.cog {
+        let parcels_changed = normalize_parcel_communes(&mut parcels, cog);
+        let addresses_changed = normalize_address_communes(&mut addresses, cog);
+        if let Some(b) = buildings.as_mut() {
+            normalize_parcel_communes(b, cog);
+        }
+        info!(dept=%dept, parcels_changed, addresses_changed, "code_insee normalized to the COG reference year");
+    }
     // L
     // Limit/Filter
-    if let Some(c) = filter_commune {
-        parcels.retain(|p| p.code_insee == *c);
-        addresses.retain(|a| a.code_insee == *c);
+    if let Some(c) = scope.filter_commune {
+        let c = match scope.cog {
+            Some(cog) => cog.normalize(c).into_owned(),
+            None => c.clone(),
+        };
+        parcels.retain(|p| p.code_insee == c);
+        addresses.retain(|a| a.code_insee == c);
+        if let Some(b) = buildings.as_mut() {
+            b.retain(|b| b.code_insee == c);
+        }
     }
-    if let Some(l) = limit_addresses {
+    if let Some(l) = scope.limit_addresses {
         if addresses.len() > l {
             addresses.truncate(l);
         }
@@ -66,6 +106,44 @@ pub fn step_match(
         return Ok(output_path);
     }
 
+    // Halo: border parcels/addresses/buildings of neighbouring departments take part in the
+    // matching, but only rows touching this department are written: a row is kept when its
+    // address or its parcel is owned, so a link across the border is in both departments' files.
+    let mut owned: Option<(HashSet<String>, HashSet<String>)> = None;
+    if let Some(halo_m) = scope.halo_m {
+        let t_halo = Instant::now();
+        let mut halo = load_halo(
+            dept,
+            staging_dir,
+            &DepartmentIndex::build(&parcels),
+            halo_m,
+            buildings.is_some(),
+        )?;
+        if let Some(cog) = scope.cog {
+            normalize_parcel_communes(&mut halo.parcels, cog);
+            normalize_address_communes(&mut halo.addresses, cog);
+            normalize_parcel_communes(&mut halo.buildings, cog);
+        }
+        info!(
+            dept=%dept,
+            halo_m,
+            halo_parcels=halo.parcels.len(),
+            halo_addresses=halo.addresses.len(),
+            halo_buildings=halo.buildings.len(),
+            duration_s=t_halo.elapsed().as_secs_f32(),
+            "halo loaded"
+        );
+        owned = Some((
+            parcels.iter().map(|p| p.id.clone()).collect(),
+            addresses.iter().map(|a| a.id.clone()).collect(),
+        ));
+        parcels.extend(halo.parcels);
+        addresses.extend(halo.addresses);
+        if let Some(b) = buildings.as_mut() {
+            b.extend(halo.buildings);
+        }
+    }
+
     let t_match = Instant::now();
     info!(
         dept=%dept,
@@ -75,7 +153,39 @@ pub fn step_match(
         fallback_max_distance_m=config.fallback_max_distance_m,
         "matching started"
     );
-    let matches = match_parcels_and_addresses_3_steps(&parcels, &addresses, config);
+    let mut matches = match_parcels_and_addresses_3_steps(
+        &parcels,
+        &addresses,
+        buildings.as_ref().map(|b| b as &dyn ParcelStore),
+        config,
+    );
+    if let Some((own_parcels, own_addresses)) = &owned {
+        let before = matches.len();
+        matches.retain(|m| {
+            own_addresses.contains(&m.id_ban)
+                || m.id_parcelle
+                    .as_ref()
+                    .is_some_and(|pid| own_parcels.contains(pid))
+        });
+        info!(dept=%dept, dropped=before - matches.len(), "halo rows between neighbours dropped");
+    }
+
+    // BAN links to parcels the matcher did not load (other departments)
+    let resolver = ParcelIdResolver::new(parcels.iter().map(|p| p.id.as_str()));
+    let links = unresolved_links(
+        addresses.iter().filter(|a| {
+            owned
+                .as_ref()
+                .is_none_or(|(_, own_addresses)| own_addresses.contains(&a.id))
+        }),
+        |pid| resolver.resolve(pid).is_some(),
+    );
+    if !links.is_empty() {
+        let (foreign, unresolved) =
+            resolve_foreign_links(dept, staging_dir, &links, config.preexisting_max_distance_m)?;
+        info!(dept=%dept, foreign_links=foreign.len(), unresolved_links=unresolved, "BAN links outside the department");
+        matches.extend(foreign);
+    }
     info!(
         dept=%dept,
         matches=matches.len(),
@@ -101,3 +211,156 @@ pub fn step_match(
 
     Ok(output_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prepare::native::{to_wkb, StagingWriter};
+    use crate::test_support::LAMBERT_93_ORIGIN;
+    use geo::{coord, Geometry, Point, Rect};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+    use std::fs::File;
+
+    const ADDRESS_EXTRAS: [&str; 3] = ["existing_link", "type_position", "source_position"];
+
+    fn stage(path: &Path, extras: &[&str], rows: &[(&str, &str, Geometry)]) {
+        let mut writer = StagingWriter::new(path, extras).unwrap();
+        for (id, code_insee, geom) in rows {
+            let nulls = vec![None; extras.len()];
+            writer
+                .push(
+                    id.to_string(),
+                    code_insee.to_string(),
+                    to_wkb(geom).unwrap(),
+                    nulls,
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    /// Rectangle `[x, x + w] x [y, y + h]` from the Lambert-93 origin.
+    fn rect(x: f64, y: f64, w: f64, h: f64) -> Geometry {
+        let (x0, y0) = LAMBERT_93_ORIGIN;
+        Geometry::Polygon(
+            Rect::new(
+                coord! {x: x0 + x, y: y0 + y},
+                coord! {x: x0 + x + w, y: y0 + y + h},
+            )
+            .to_polygon(),
+        )
+    }
+
+    fn point(x: f64, y: f64) -> Geometry {
+        let (x0, y0) = LAMBERT_93_ORIGIN;
+        Geometry::Point(Point::new(x0 + x, y0 + y))
+    }
+
+    /// (id_ban, id_parcelle, match_type, id_batiment) of a matches file.
+    fn written(path: &Path) -> Vec<(String, String, String, Option<String>)> {
+        let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+        reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                let row = row.unwrap();
+                (
+                    row.get_string(0).unwrap().clone(),
+                    row.get_string(1).cloned().unwrap_or_default(),
+                    row.get_string(2).unwrap().clone(),
+                    row.get_string(9).ok().cloned(),
+                )
+            })
+            .collect()
+    }
+
+    /// Department 01: P1, an empty strip S along the border and P3 to the north.
+    /// Department 02: Q1, built, across the border from S; address b1 5 m north of P3.
+    #[test]
+    fn halo_keeps_rows_touching_the_department() {
+        let dir = std::env::temp_dir().join(format!("ban_cadastre_halo_{}", std::process::id()));
+        let (staging, results) = (dir.join("staging"), dir.join("results"));
+        stage(
+            &staging.join("parcelles_01.parquet"),
+            &[],
+            &[
+                ("P1", "01001", rect(0.0, 0.0, 10.0, 10.0)),
+                ("S", "01001", rect(10.0, 0.0, 2.0, 10.0)),
+                ("P3", "01001", rect(0.0, 100.0, 10.0, 10.0)),
+            ],
+        );
+        stage(
+            &staging.join("adresses_01.parquet"),
+            &ADDRESS_EXTRAS,
+            &[
+                ("a1", "01001", point(5.0, 5.0)),
+                ("c", "01001", point(11.0, 5.0)),
+                ("a2", "01001", point(19.0, 5.0)),
+            ],
+        );
+        stage(&staging.join("batiments_01.parquet"), &[], &[]);
+        stage(
+            &staging.join("parcelles_02.parquet"),
+            &[],
+            &[("Q1", "02001", rect(12.0, 0.0, 10.0, 10.0))],
+        );
+        stage(
+            &staging.join("adresses_02.parquet"),
+            &ADDRESS_EXTRAS,
+            &[("b1", "02001", point(5.0, 115.0))],
+        );
+        stage(
+            &staging.join("batiments_02.parquet"),
+            &[],
+            &[("B1", "02001", rect(17.0, 2.0, 4.0, 6.0))],
+        );
+
+        let config = MatchConfig::builder()
+            .prefer_built_parcels(10.0)
+            .build()
+            .unwrap();
+        let scope = MatchScope {
+            filter_commune: None,
+            limit_addresses: None,
+            halo_m: Some(50.0),
+            cog: None,
+        };
+        let run = |dept| {
+            let path = step_match(dept, &staging, &results, &config, false, &scope).unwrap();
+            written(&path)
+        };
+        let (rows_01, rows_02) = (run("01"), run("02"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let find = |rows: &[(String, String, String, Option<String>)], id_ban: &str, pid: &str| {
+            rows.iter()
+                .find(|r| r.0 == id_ban && r.1 == pid)
+                .map(|r| (r.2.clone(), r.3.clone()))
+        };
+        let built = Some("B1".to_string());
+        // Own address inside a neighbour parcel, in both departments' files.
+        for rows in [&rows_01, &rows_02] {
+            assert_eq!(
+                find(rows, "a2", "Q1"),
+                Some(("Inside".into(), built.clone()))
+            );
+        }
+        // Own parcel matched only by a neighbour address: no Step 3 row, the BorderNear is kept.
+        assert_eq!(
+            find(&rows_01, "b1", "P3"),
+            Some(("BorderNear".into(), None))
+        );
+        assert!(!rows_01.iter().any(|r| r.1 == "P3" && r.0 != "b1"));
+        // Neighbour buildings: the empty strip gives way to the built parcel across the border.
+        assert_eq!(find(&rows_01, "c", "S"), None);
+        assert_eq!(
+            find(&rows_01, "c", "Q1"),
+            Some(("BorderNear".into(), built))
+        );
+        // Rows between neighbour objects only are left out.
+        assert!(rows_01
+            .iter()
+            .all(|r| r.0.starts_with(['a', 'c']) || r.1.starts_with('P')));
+    }
+}
@@ -1,18 +1,24 @@
 pub mod aggregate;
 pub mod download;
+pub mod dvf;
+pub mod foreign_links;
+pub mod halo;
 pub mod match_step;
-pub mod prepare;
 pub mod qa;
 pub mod state;
 pub mod status;
 
 use crate::cli::PipelineArgs;
 use crate::pipeline::state::BatchState;
-use crate::structures::MatchConfig;
+use crate::prepare::{self, PrepareBackend, SpatialExtension};
 use anyhow::{Context, Result};
+use ban_cadastre::confidence::load_scorer;
+use ban_cadastre::crs::department_crs;
+use ban_cadastre::structures::MatchConfig;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use tracing::{error, info, instrument, warn};
 
@@ -26,6 +32,41 @@ pub struct PipelineOutcome {
     pub output_dir: PathBuf,
 }
 
+/// Download + prepare (Steps A and B) of one department.
+fn stage_department(
+    dept: &str,
+    raw_dir: &Path,
+    staging_dir: &Path,
+    force: bool,
+    buildings: bool,
+    backend: PrepareBackend,
+    spatial: &SpatialExtension,
+) -> Result<()> {
+    // Step A: Download
+    let t0 = Instant::now();
+    download::step_download(dept, raw_dir, force)?;
+    if buildings {
+        download::step_download_buildings(dept, raw_dir, force)?;
+    }
+    info!(
+        dept=%dept,
+        step="download",
+        duration_s=t0.elapsed().as_secs_f32(),
+        "step completed"
+    );
+
+    // Step B: Prepare
+    prepare::prepare_department(
+        dept,
+        raw_dir,
+        staging_dir,
+        force,
+        buildings,
+        backend,
+        spatial,
+    )
+}
+
 #[instrument(skip(args))]
 pub fn run_pipeline(args: PipelineArgs) -> Result<PipelineOutcome> {
     info!(
@@ -84,7 +125,50 @@ pub fn run_pipeline(args: PipelineArgs) -> Result<PipelineOutcome> {
     let total = depts.len();
     info!(total_departments = total, "loaded departments");
 
-    let match_config = MatchConfig::default();
+    let mut match_config = MatchConfig {
+        emit_unmatched_addresses: args.emit_unmatched,
+        border_candidates: if args.candidates {
+            args.num_neighbors
+        } else {
+            1
+        },
+        preexisting_max_distance_m: args.preexisting_max_distance_m,
+        confidence_scorer: load_scorer(args.confidence_model.as_deref())?,
+        stacked_min_addresses: args.stacked_min_addresses,
+        exclude_stacked: args.exclude_stacked,
+        prefer_built_parcels: args.buildings,
+        building_max_distance_m: args.building_max_distance_m,
+        ..MatchConfig::default()
+    };
+    args.cross_commune.apply(&mut match_config);
+    args.positions.apply(&mut match_config);
+    let cog = args.cog.load()?;
+    let spatial = SpatialExtension::from_path(args.spatial_extension.clone());
+
+    // 3bis. Halo: neighbours must be staged before any department is matched.
+    let mut staged: HashSet<String> = HashSet::new();
+    if args.halo_m.is_some() {
+        for dept in &depts {
+            if args.resume && state.is_completed(dept) {
+                continue;
+            }
+            match stage_department(
+                dept,
+                &raw_dir,
+                &staging_dir,
+                args.force,
+                args.buildings,
+                args.prepare_backend,
+                &spatial,
+            ) {
+                Ok(()) => {
+                    staged.insert(dept.clone());
+                }
+                // retried (and recorded as failed) by the main loop
+                Err(e) => warn!(dept=%dept, error=%e, "halo pre-pass: staging failed"),
+            }
+        }
+    }
 
     // 4. Loop
     for (idx, dept) in depts.into_iter().enumerate() {
@@ -103,34 +187,18 @@ pub fn run_pipeline(args: PipelineArgs) -> Result<PipelineOutcome> {
 
         let res =
             std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| -> Result<qa::QaSummary> {
-                // Step A: Download
-                let t0 = Instant::now();
-                download::step_download(&dept, &raw_dir, args.force)?;
-                info!(
-                    dept=%dept,
-                    step="download",
-                    duration_s=t0.elapsed().as_secs_f32(),
-                    "step completed"
-                );
-
-                // Step B: Prepare
-                let t1 = Instant::now();
-                let p_in = raw_dir.join(format!("cadastre-{}-parcelles.json", dept));
-                let p_out = staging_dir.join(format!("parcelles_{}.parquet", dept));
-                if args.force || !p_out.exists() {
-                    prepare::step_prepare_parcels(&p_in, &p_out)?;
-                }
-                let a_in = raw_dir.join(format!("adresses-{}.csv", dept));
-                let a_out = staging_dir.join(format!("adresses_{}.parquet", dept));
-                if args.force || !a_out.exists() {
-                    prepare::step_prepare_addresses(&a_in, &a_out)?;
+                // Steps A+B: Download + Prepare (unless done by the halo pre-pass)
+                if !staged.contains(&dept) {
+                    stage_department(
+                        &dept,
+                        &raw_dir,
+                        &staging_dir,
+                        args.force,
+                        args.buildings,
+                        args.prepare_backend,
+                        &spatial,
+                    )?;
                 }
-                info!(
-                    dept=%dept,
-                    step="prepare",
-                    duration_s=t1.elapsed().as_secs_f32(),
-                    "step completed"
-                );
 
                 // Step C: Match
                 let t2 = Instant::now();
@@ -140,8 +208,12 @@ pub fn run_pipeline(args: PipelineArgs) -> Result<PipelineOutcome> {
                     &batch_results_dir,
                     &match_config,
                     args.quick_qa,
-                    args.filter_commune.as_ref(),
-                    args.limit_addresses,
+                    &match_step::MatchScope {
+                        filter_commune: args.filter_commune.as_ref(),
+                        limit_addresses: args.limit_addresses,
+                        halo_m: args.halo_m,
+                        cog: cog.as_ref(),
+                    },
                 )?;
                 info!(
                     dept=%dept,
@@ -152,9 +224,32 @@ pub fn run_pipeline(args: PipelineArgs) -> Result<PipelineOutcome> {
 
                 // Step D: QA
                 let t3 = Instant::now();
-                let summary = qa::step_qa(&dept, &staging_dir, &batch_results_dir, &final_output)?;
+                let mut summary = qa::step_qa(
+                    &dept,
+                    &staging_dir,
+                    &batch_results_dir,
+                    &final_output,
+                    cog.as_ref(),
+                )?;
                 info!("✨ QA step completed in {:.1}s", t3.elapsed().as_secs_f32());
 
+                // Step D bis: DVF comparison (department file present in --dvf-dir)
+                if let Some(path) = args
+                    .dvf_dir
+                    .as_deref()
+                    .and_then(|dir| dvf::department_file(dir, &dept))
+                {
+                    summary.dvf_agreement_pct = Some(dvf::step_dvf(
+                        &dept,
+                        &path,
+                        &staging_dir,
+                        &batch_results_dir,
+                        &final_output,
+                        &department_crs(&dept),
+                        match_config.address_max_distance_m,
+                    )?);
+                }
+
                 Ok(summary)
             }));
 
@@ -191,6 +286,13 @@ pub fn run_pipeline(args: PipelineArgs) -> Result<PipelineOutcome> {
                     coverage_pct=summary.coverage_pct,
                     coverage_band=coverage_band,
                     avg_confidence=summary.avg_confidence,
+                    cross_commune_links=summary.cross_commune_links,
+                    link_agreement_pct=summary.link_agreement_pct,
+                    dvf_agreement_pct=?summary.dvf_agreement_pct,
+                    stacked_addresses=summary.stacked_addresses,
+                    foreign_links=summary.foreign_links,
+                    unresolved_links=summary.unresolved_links,
+                    normalized_links=summary.normalized_links,
                     coverage_lt_5m_pct=pct_5,
                     coverage_lt_50m_pct=pct_50,
                     "department processed"
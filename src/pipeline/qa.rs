@@ -1,5 +1,7 @@
 use anyhow::{Context, Result};
-use duckdb::{Config, Connection};
+use ban_cadastre::cog::CogHistory;
+use duckdb::{params, Config, Connection};
+use std::collections::HashSet;
 use std::path::Path;
 
 #[allow(dead_code)]
@@ -9,8 +11,35 @@ pub struct QaSummary {
     pub coverage_pct: f64,
     pub dist_tier_pcts: Vec<(f64, f64)>,
     pub avg_confidence: f64,
+    pub cross_commune_links: i64,
+    /// Share of BAN-linked addresses whose geometric parcel agrees with the link,
+    /// among addresses having both (agree / (agree + disagree)).
+    pub link_agreement_pct: f64,
+    /// Addresses of the department tagged as stacked by the matcher (`stack_size`).
+    pub stacked_addresses: i64,
+    /// BAN links resolved against a parcel of another department.
+    pub foreign_links: i64,
+    /// BAN links whose parcel was found in no staged department.
+    pub unresolved_links: i64,
+    /// Resolved BAN links whose id was normalized to a different Etalab id.
+    pub normalized_links: i64,
+    /// DVF parcels agreeing with the parcels of their BAN address (agree / (agree + disagree)),
+    /// when a DVF file was given (`dvf::step_dvf`).
+    pub dvf_agreement_pct: Option<f64>,
 }
 
+/// Columns added to `matches_<DEP>.parquet` after its first layout, with the value assumed
+/// for files written before them.
+const MATCHES_OPTIONAL_COLUMNS: [(&str, &str); 7] = [
+    ("hint_parcelle", "NULL::VARCHAR"),
+    ("rank", "1::UINTEGER"),
+    ("runner_up_id", "NULL::VARCHAR"),
+    ("margin_m", "NULL::FLOAT"),
+    ("id_batiment", "NULL::VARCHAR"),
+    ("stack_size", "NULL::UINTEGER"),
+    ("id_parcelle_ban", "NULL::VARCHAR"),
+];
+
 fn sql_path(path: &Path) -> String {
     path.to_string_lossy()
         .replace('\\', "/")
@@ -22,6 +51,7 @@ pub fn step_qa(
     staging_dir: &Path,
     results_dir: &Path,
     output_dir: &Path,
+    cog: Option<&CogHistory>,
 ) -> Result<QaSummary> {
     let matches_path = results_dir.join(format!("matches_{}.parquet", dept));
     let parcel_src = staging_dir.join(format!("parcelles_{}.parquet", dept));
@@ -37,18 +67,68 @@ pub fn step_qa(
     let config = Config::default();
     let conn = Connection::open_in_memory_with_flags(config).context("Failed to open DuckDB QA")?;
 
+    // Matches written before optional columns existed get their default value (rank 1, NULL).
+    let present: HashSet<String> = conn
+        .prepare(&format!(
+            "SELECT name FROM parquet_schema('{}')",
+            sql_path(&matches_path)
+        ))?
+        .query_map([], |r| r.get::<_, String>(0))?
+        .collect::<Result<_, _>>()
+        .context("Matches schema query")?;
+    let defaults: String = MATCHES_OPTIONAL_COLUMNS
+        .iter()
+        .filter(|(name, _)| !present.contains(*name))
+        .map(|(name, default)| format!(", {} AS {}", default, name))
+        .collect();
     conn.execute(
         &format!(
-            "CREATE VIEW matches AS SELECT * FROM read_parquet('{}')",
+            "CREATE VIEW matches AS SELECT * FROM (SELECT *{} FROM read_parquet('{}')) WHERE rank = 1",
+            defaults,
             sql_path(&matches_path)
         ),
         [],
     )
     .context("Create view matches")?;
 
+    // code_insee → COG reference year code (only codes that change; empty without COG history)
+    conn.execute(
+        "CREATE TABLE cog_communes (code_insee VARCHAR, code_ref VARCHAR)",
+        [],
+    )?;
+    if let Some(cog) = cog {
+        let codes: Vec<String> = conn
+            .prepare(&format!(
+                r#"
+SELECT DISTINCT CAST(code_insee AS VARCHAR) FROM read_parquet('{}')
+UNION
+SELECT DISTINCT CAST(code_insee AS VARCHAR) FROM read_parquet('{}')
+"#,
+                sql_path(&parcel_src),
+                sql_path(&address_src)
+            ))?
+            .query_map([], |r| r.get::<_, Option<String>>(0))?
+            .filter_map(|r| r.transpose())
+            .collect::<Result<_, _>>()
+            .context("COG communes query")?;
+        let mut appender = conn.appender("cog_communes")?;
+        for code in &codes {
+            let code_ref = cog.normalize(code);
+            if code_ref != code.as_str() {
+                appender.append_row(params![code, code_ref.as_ref()])?;
+            }
+        }
+        appender.flush()?;
+    }
+
     conn.execute(
         &format!(
-            "CREATE VIEW parcels AS SELECT * FROM read_parquet('{}')",
+            r#"
+CREATE VIEW parcels AS
+SELECT s.* REPLACE (COALESCE(c.code_ref, CAST(s.code_insee AS VARCHAR)) AS code_insee)
+FROM read_parquet('{}') s
+LEFT JOIN cog_communes c ON c.code_insee = CAST(s.code_insee AS VARCHAR)
+"#,
             sql_path(&parcel_src)
         ),
         [],
@@ -57,7 +137,12 @@ pub fn step_qa(
 
     conn.execute(
         &format!(
-            "CREATE VIEW addresses AS SELECT * FROM read_parquet('{}')",
+            r#"
+CREATE VIEW addresses AS
+SELECT s.* REPLACE (COALESCE(c.code_ref, CAST(s.code_insee AS VARCHAR)) AS code_insee)
+FROM read_parquet('{}') s
+LEFT JOIN cog_communes c ON c.code_insee = CAST(s.code_insee AS VARCHAR)
+"#,
             sql_path(&address_src)
         ),
         [],
@@ -125,6 +210,7 @@ SELECT
   (count(DISTINCT id_parcelle)::DOUBLE / {tp}::DOUBLE * 100.0) as coverage_pct
 FROM matches
 WHERE id_parcelle IS NOT NULL
+  AND id_parcelle IN (SELECT id FROM parcels)
   AND (match_type IN ('PreExisting','Inside') OR distance_m <= {t})
 "#,
                 t = t,
@@ -176,6 +262,7 @@ WITH ranked AS (
       WHEN 'Inside' THEN 1
       WHEN 'BorderNear' THEN 2
       WHEN 'FallbackNearest' THEN 3
+      WHEN 'PreExistingFar' THEN 4
       ELSE 100
     END AS prio,
     ROW_NUMBER() OVER (
@@ -186,6 +273,7 @@ WITH ranked AS (
           WHEN 'Inside' THEN 1
           WHEN 'BorderNear' THEN 2
           WHEN 'FallbackNearest' THEN 3
+          WHEN 'PreExistingFar' THEN 4
           ELSE 100
         END ASC,
         distance_m ASC,
@@ -289,6 +377,340 @@ ORDER BY coverage_pct ASC, total_parcels DESC
     )
     .context("QA Worst communes export")?;
 
+    // 10.5 QA Cross-commune links (address code_insee != parcel code_insee)
+    // Schema: id_ban, code_insee_adresse, id_parcelle, code_insee_parcelle, match_type, distance_m, confidence
+    conn.execute(
+        r#"
+CREATE TABLE cross_commune AS
+SELECT
+  m.id_ban,
+  CAST(a.code_insee AS VARCHAR) AS code_insee_adresse,
+  m.id_parcelle,
+  CAST(p.code_insee AS VARCHAR) AS code_insee_parcelle,
+  m.match_type,
+  m.distance_m,
+  m.confidence
+FROM matches m
+JOIN addresses a ON a.id = m.id_ban
+JOIN parcels p ON p.id = m.id_parcelle
+WHERE m.id_parcelle IS NOT NULL
+  AND CAST(a.code_insee AS VARCHAR) != CAST(p.code_insee AS VARCHAR)
+"#,
+        [],
+    )
+    .context("QA Cross-commune calc")?;
+
+    let cross_commune_links: i64 =
+        conn.query_row("SELECT count(*) FROM cross_commune", [], |r| r.get(0))?;
+
+    let cross_csv = output_dir.join(format!("qa_cross_commune_{}.csv", dept));
+    conn.execute(
+        &format!(
+            r#"
+COPY (
+  SELECT *
+  FROM cross_commune
+  ORDER BY
+    CASE match_type
+      WHEN 'PreExisting' THEN 0
+      WHEN 'Inside' THEN 1
+      WHEN 'BorderNear' THEN 2
+      WHEN 'FallbackNearest' THEN 3
+      WHEN 'PreExistingFar' THEN 4
+      ELSE 100
+    END ASC,
+    distance_m DESC,
+    id_ban ASC
+) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            sql_path(&cross_csv)
+        ),
+        [],
+    )
+    .context("QA Cross-commune export")?;
+
+    // 10.6 QA PreExisting far (BAN cad_parcelles links beyond preexisting_max_distance_m)
+    // Schema: id_ban, id_parcelle, distance_m, confidence
+    let pre_far_csv = output_dir.join(format!("qa_preexisting_far_{}.csv", dept));
+    conn.execute(
+        &format!(
+            r#"
+COPY (
+  SELECT id_ban, id_parcelle, distance_m, confidence
+  FROM matches
+  WHERE match_type = 'PreExistingFar'
+  ORDER BY distance_m DESC, id_ban ASC
+) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            sql_path(&pre_far_csv)
+        ),
+        [],
+    )
+    .context("QA PreExisting far export")?;
+
+    // 10.7 QA Ambiguity (runner-up margin on BorderNear / FallbackNearest links)
+    // Schema: match_type, total_links, margin_lt_1m, margin_1_2m, margin_2_5m, clear
+    let ambiguity_csv = output_dir.join(format!("qa_ambiguity_{}.csv", dept));
+    conn.execute(
+        &format!(
+            r#"
+COPY (
+SELECT
+  match_type,
+  count(*) AS total_links,
+  count(*) FILTER (WHERE margin_m < 1) AS margin_lt_1m,
+  count(*) FILTER (WHERE margin_m >= 1 AND margin_m < 2) AS margin_1_2m,
+  count(*) FILTER (WHERE margin_m >= 2 AND margin_m < 5) AS margin_2_5m,
+  count(*) FILTER (WHERE margin_m IS NULL OR margin_m >= 5) AS clear
+FROM matches
+WHERE match_type IN ('BorderNear', 'FallbackNearest')
+GROUP BY match_type
+ORDER BY match_type
+) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            sql_path(&ambiguity_csv)
+        ),
+        [],
+    )
+    .context("QA Ambiguity export")?;
+
+    // 10.8 QA Link agreement (BAN cad_parcelles vs geometric Inside/BorderNear choice)
+    // Geometric choice: Inside parcels (incl. PreExisting at distance 0, whose Inside row is not
+    // emitted), else the BorderNear parcel.
+    conn.execute(
+        r#"
+CREATE TABLE link_agreement AS
+WITH link_p AS (
+  SELECT id_ban, id_parcelle
+  FROM matches
+  WHERE match_type IN ('PreExisting', 'PreExistingFar')
+),
+inside_p AS (
+  SELECT id_ban, id_parcelle
+  FROM matches
+  WHERE match_type = 'Inside'
+     OR (match_type = 'PreExisting' AND distance_m = 0)
+),
+geo_p AS (
+  SELECT id_ban, id_parcelle FROM inside_p
+  UNION ALL
+  SELECT b.id_ban, b.id_parcelle
+  FROM matches b
+  WHERE b.match_type = 'BorderNear'
+    AND b.id_ban NOT IN (SELECT id_ban FROM inside_p)
+),
+link_agg AS (SELECT DISTINCT id_ban FROM link_p),
+geo_agg AS (SELECT DISTINCT id_ban FROM geo_p),
+agree_agg AS (
+  SELECT DISTINCT g.id_ban
+  FROM geo_p g
+  JOIN link_p l ON l.id_ban = g.id_ban AND l.id_parcelle = g.id_parcelle
+)
+SELECT
+  CAST(a.id AS VARCHAR) AS id_ban,
+  CASE
+    WHEN ag.id_ban IS NOT NULL THEN 'agree'
+    WHEN la.id_ban IS NOT NULL AND ga.id_ban IS NOT NULL THEN 'disagree'
+    WHEN ga.id_ban IS NOT NULL THEN 'geometric_only'
+    WHEN la.id_ban IS NOT NULL THEN 'link_only'
+    ELSE 'none'
+  END AS agreement
+FROM addresses a
+LEFT JOIN link_agg la ON la.id_ban = CAST(a.id AS VARCHAR)
+LEFT JOIN geo_agg ga ON ga.id_ban = CAST(a.id AS VARCHAR)
+LEFT JOIN agree_agg ag ON ag.id_ban = CAST(a.id AS VARCHAR)
+WHERE a.existing_link IS NOT NULL
+"#,
+        [],
+    )
+    .context("QA Link agreement calc")?;
+
+    // Schema: agreement, count, pct
+    let agreement_csv = output_dir.join(format!("qa_link_agreement_{}.csv", dept));
+    conn.execute(
+        &format!(
+            r#"
+COPY (
+SELECT
+  agreement,
+  count(*) AS count,
+  (count(*)::DOUBLE / sum(count(*)) OVER () * 100.0) AS pct
+FROM link_agreement
+GROUP BY agreement
+ORDER BY
+  CASE agreement
+    WHEN 'agree' THEN 0
+    WHEN 'disagree' THEN 1
+    WHEN 'geometric_only' THEN 2
+    WHEN 'link_only' THEN 3
+    ELSE 4
+  END
+) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            sql_path(&agreement_csv)
+        ),
+        [],
+    )
+    .context("QA Link agreement export")?;
+
+    let link_agreement_pct: f64 = conn
+        .query_row(
+            r#"
+SELECT COALESCE(
+  count(*) FILTER (WHERE agreement = 'agree')::DOUBLE
+    / NULLIF(count(*) FILTER (WHERE agreement IN ('agree', 'disagree')), 0)::DOUBLE * 100.0,
+  0.0
+)
+FROM link_agreement
+"#,
+            [],
+            |r| r.get(0),
+        )
+        .context("Link agreement rate query")?;
+
+    // 10.9 QA Stacked addresses (tagged by the matcher: `stack_size`, see MatchConfig::stacked_min_addresses)
+    // Schema: code_insee, addresses, preexisting_links, inside_links, border_near_links, fallback_links
+    // (one row per stacked coordinate; addresses = stack_size; links = rank-1 rows of its addresses)
+    conn.execute(
+        r#"
+CREATE TABLE stacked_addresses AS
+SELECT CAST(a.id AS VARCHAR) AS id_ban, a.geom, CAST(a.code_insee AS VARCHAR) AS code_insee, m.stack_size
+FROM (SELECT id_ban, max(stack_size) AS stack_size FROM matches WHERE stack_size IS NOT NULL GROUP BY id_ban) m
+JOIN addresses a ON CAST(a.id AS VARCHAR) = m.id_ban
+"#,
+        [],
+    )
+    .context("QA Stacked addresses")?;
+    conn.execute(
+        r#"
+CREATE TABLE stacked AS
+SELECT geom, min(code_insee) AS code_insee, max(stack_size) AS addresses
+FROM stacked_addresses
+GROUP BY geom
+"#,
+        [],
+    )
+    .context("QA Stacked calc")?;
+
+    let stacked_addresses: i64 =
+        conn.query_row("SELECT count(*) FROM stacked_addresses", [], |r| r.get(0))?;
+
+    let stacked_csv = output_dir.join(format!("qa_stacked_{}.csv", dept));
+    conn.execute(
+        &format!(
+            r#"
+COPY (
+SELECT
+  s.code_insee,
+  s.addresses,
+  count(*) FILTER (WHERE m.match_type = 'PreExisting') AS preexisting_links,
+  count(*) FILTER (WHERE m.match_type = 'Inside') AS inside_links,
+  count(*) FILTER (WHERE m.match_type = 'BorderNear') AS border_near_links,
+  count(*) FILTER (WHERE m.match_type = 'FallbackNearest') AS fallback_links
+FROM stacked s
+JOIN stacked_addresses a ON a.geom = s.geom
+LEFT JOIN matches m ON m.id_ban = a.id_ban
+GROUP BY s.geom, s.code_insee, s.addresses
+ORDER BY s.addresses DESC, fallback_links DESC, s.code_insee
+) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            sql_path(&stacked_csv)
+        ),
+        [],
+    )
+    .context("QA Stacked export")?;
+
+    // 10.10 QA BAN link resolution (cad_parcelles of the department's addresses)
+    // local = own parcel, foreign = parcel of another staged department, unresolved = parcel not found;
+    // normalized = resolved through an id variant (PreExisting rows carry the BAN id in id_parcelle_ban)
+    conn.execute(
+        r#"
+CREATE TABLE link_resolution AS
+WITH links AS (
+  SELECT DISTINCT CAST(a.id AS VARCHAR) AS id_ban, trim(l.pid) AS id_parcelle
+  FROM addresses a,
+       unnest(regexp_split_to_array(CAST(a.existing_link AS VARCHAR), '[;|,]')) AS l(pid)
+  WHERE a.existing_link IS NOT NULL
+    AND trim(l.pid) != ''
+),
+resolved AS (
+  SELECT DISTINCT id_ban, COALESCE(id_parcelle_ban, id_parcelle) AS link, id_parcelle
+  FROM matches
+  WHERE match_type IN ('PreExisting', 'PreExistingFar')
+)
+SELECT
+  l.id_ban,
+  l.id_parcelle,
+  CASE WHEN starts_with(upper(l.id_parcelle), '97') THEN upper(left(l.id_parcelle, 3))
+       ELSE upper(left(l.id_parcelle, 2)) END AS dept_parcelle,
+  CASE
+    WHEN r.id_ban IS NULL THEN 'unresolved'
+    WHEN r.id_parcelle IN (SELECT id FROM parcels) THEN 'local'
+    ELSE 'foreign'
+  END AS status,
+  COALESCE(r.id_parcelle != l.id_parcelle, false) AS normalized
+FROM links l
+LEFT JOIN resolved r ON r.id_ban = l.id_ban AND r.link = l.id_parcelle
+"#,
+        [],
+    )
+    .context("QA Link resolution calc")?;
+
+    let (foreign_links, unresolved_links, normalized_links): (i64, i64, i64) = conn.query_row(
+        r#"
+SELECT
+  count(*) FILTER (WHERE status = 'foreign'),
+  count(*) FILTER (WHERE status = 'unresolved'),
+  count(*) FILTER (WHERE normalized)
+FROM link_resolution
+"#,
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+    )?;
+
+    // Schema: status, dept_parcelle, links, normalized_links
+    let resolution_csv = output_dir.join(format!("qa_link_resolution_{}.csv", dept));
+    conn.execute(
+        &format!(
+            r#"
+COPY (
+SELECT
+  status,
+  dept_parcelle,
+  count(*) AS links,
+  count(*) FILTER (WHERE normalized) AS normalized_links
+FROM link_resolution
+GROUP BY status, dept_parcelle
+ORDER BY
+  CASE status WHEN 'local' THEN 0 WHEN 'foreign' THEN 1 ELSE 2 END,
+  links DESC,
+  dept_parcelle
+) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            sql_path(&resolution_csv)
+        ),
+        [],
+    )
+    .context("QA Link resolution export")?;
+
+    // Schema: id_ban, id_parcelle, dept_parcelle
+    let unresolved_csv = output_dir.join(format!("qa_unresolved_links_{}.csv", dept));
+    conn.execute(
+        &format!(
+            r#"
+COPY (
+SELECT id_ban, id_parcelle, dept_parcelle
+FROM link_resolution
+WHERE status = 'unresolved'
+ORDER BY id_parcelle, id_ban
+) TO '{}' (FORMAT 'CSV', HEADER)
+"#,
+            sql_path(&unresolved_csv)
+        ),
+        [],
+    )
+    .context("QA Unresolved links export")?;
+
     // QA addresses (sentinel unified to 'None')
     let addr_csv = output_dir.join(format!("qa_addresses_{}.csv", dept));
     conn.execute(
@@ -305,6 +727,7 @@ WITH m_ranked AS (
       WHEN 'Inside' THEN 1
       WHEN 'BorderNear' THEN 2
       WHEN 'FallbackNearest' THEN 3
+      WHEN 'PreExistingFar' THEN 4
       ELSE 100
     END as priority,
     ROW_NUMBER() OVER (PARTITION BY id_ban ORDER BY
@@ -313,6 +736,7 @@ WITH m_ranked AS (
         WHEN 'Inside' THEN 1
         WHEN 'BorderNear' THEN 2
         WHEN 'FallbackNearest' THEN 3
+        WHEN 'PreExistingFar' THEN 4
         ELSE 100
       END ASC,
       distance_m ASC
@@ -337,6 +761,7 @@ SELECT
   count(*) FILTER (WHERE res_type = 'Inside') as res_inside,
   count(*) FILTER (WHERE res_type = 'BorderNear') as res_border_near,
   count(*) FILTER (WHERE res_type = 'FallbackNearest') as res_fallback,
+  count(*) FILTER (WHERE res_type = 'PreExistingFar') as res_pre_far,
   count(*) FILTER (WHERE res_type = 'None') as res_none,
   count(*) FILTER (WHERE res_type != 'None' AND dist <= 5) as dist_0_5,
   count(*) FILTER (WHERE res_type != 'None' AND dist > 5 AND dist <= 15) as dist_5_15,
@@ -366,6 +791,7 @@ WITH ranked AS (
           WHEN 'Inside' THEN 1
           WHEN 'BorderNear' THEN 2
           WHEN 'FallbackNearest' THEN 3
+          WHEN 'PreExistingFar' THEN 4
           ELSE 100
         END ASC,
         distance_m ASC
@@ -395,5 +821,12 @@ WHERE rn = 1
         coverage_pct,
         dist_tier_pcts,
         avg_confidence: avg_conf,
+        cross_commune_links,
+        link_agreement_pct,
+        stacked_addresses,
+        foreign_links,
+        unresolved_links,
+        normalized_links,
+        dvf_agreement_pct: None,
     })
 }
@@ -0,0 +1,287 @@
+//! Staging preparation (Step B): Etalab GeoJSON and BAN / point CSV or Parquet to the staging
+//! Parquet layout read by `loader`. Two backends: `sql` (DuckDB spatial, feature `duckdb-qa`)
+//! and `native` (pure Rust, always built).
+
+pub mod native;
+#[cfg(feature = "duckdb-qa")]
+pub mod sql;
+
+use crate::cli::{PrepareArgs, PreparePointsArgs};
+use anyhow::{anyhow, bail, Context, Result};
+use ban_cadastre::crs::department_crs;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{info, instrument, warn};
+
+/// Engine running the prepare steps. DuckDB by default when built with `duckdb-qa`, native
+/// otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PrepareBackend {
+    /// DuckDB spatial (`sql`, feature `duckdb-qa`).
+    #[cfg_attr(feature = "duckdb-qa", default)]
+    Duckdb,
+    /// Pure Rust (`native`): no DuckDB, reads the `.gz` downloads directly.
+    #[cfg_attr(not(feature = "duckdb-qa"), default)]
+    Native,
+}
+
+impl PrepareBackend {
+    /// Rejects `duckdb` in a build without the `duckdb-qa` feature.
+    pub fn ensure_available(self) -> Result<()> {
+        if cfg!(not(feature = "duckdb-qa")) && self == Self::Duckdb {
+            bail!("--prepare-backend duckdb needs a build with the duckdb-qa feature; use native");
+        }
+        Ok(())
+    }
+}
+
+/// Origin of the DuckDB `spatial` extension used by the prepare steps.
+// Only read by the DuckDB backend.
+#[cfg_attr(not(feature = "duckdb-qa"), allow(dead_code))]
+#[derive(Debug, Clone, Default)]
+pub enum SpatialExtension {
+    /// `INSTALL spatial` from the DuckDB extension repository (network on first use).
+    #[default]
+    Install,
+    /// Local `spatial.duckdb_extension` file, loaded without network access.
+    Path(PathBuf),
+}
+
+impl SpatialExtension {
+    pub fn from_path(path: Option<PathBuf>) -> Self {
+        path.map(Self::Path).unwrap_or_default()
+    }
+}
+
+/// Source columns of `step_prepare_addresses` (both backends). The defaults are the BAN CSV
+/// names; a JSON spec maps any point dataset (SIRENE, DPE, permits...) onto the address staging
+/// schema. Keys left out keep their default, `null` marks a column the dataset does not have;
+/// see `resolve` for mapped columns missing from the input.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AddressColumns {
+    pub id: String,
+    pub code_insee: String,
+    /// Coordinates already in the working CRS of the department.
+    pub x: Option<String>,
+    pub y: Option<String>,
+    /// CRS84 coordinates, projected when `x`/`y` are missing.
+    pub lon: Option<String>,
+    pub lat: Option<String>,
+    /// Parcel id(s) already linked by the source (`|`-separated), staged as `existing_link`.
+    pub existing_link: Option<String>,
+    pub type_position: Option<String>,
+    pub source_position: Option<String>,
+}
+
+impl Default for AddressColumns {
+    fn default() -> Self {
+        let some = |name: &str| Some(name.to_string());
+        Self {
+            id: "id".to_string(),
+            code_insee: "code_insee".to_string(),
+            x: some("x"),
+            y: some("y"),
+            lon: some("lon"),
+            lat: some("lat"),
+            existing_link: some("cad_parcelles"),
+            type_position: some("type_position"),
+            source_position: some("source_position"),
+        }
+    }
+}
+
+impl AddressColumns {
+    /// BAN columns, or the JSON spec at `path`.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let file = fs::File::open(path)
+            .with_context(|| format!("Failed to open column mapping {:?}", path))?;
+        serde_json::from_reader(file).with_context(|| format!("Invalid column mapping {:?}", path))
+    }
+
+    /// Mapping against the header of `input` (`available`, names compared trimmed): `id`,
+    /// `code_insee` and one `x`/`y` or `lon`/`lat` pair must exist; other mapped columns absent
+    /// from the input are unmapped, so they are staged as null by both backends.
+    pub fn resolve(&self, input: &Path, available: &[&str]) -> Result<Self> {
+        let find = |name: &Option<String>| {
+            let name = name.as_deref()?;
+            available
+                .iter()
+                .find(|h| h.trim() == name)
+                .map(|h| h.to_string())
+        };
+        let required = |name: &String| {
+            find(&Some(name.clone()))
+                .ok_or_else(|| anyhow!("Address input {:?}: missing column {}", input, name))
+        };
+        let resolved = Self {
+            id: required(&self.id)?,
+            code_insee: required(&self.code_insee)?,
+            x: find(&self.x),
+            y: find(&self.y),
+            lon: find(&self.lon),
+            lat: find(&self.lat),
+            existing_link: find(&self.existing_link),
+            type_position: find(&self.type_position),
+            source_position: find(&self.source_position),
+        };
+        let projected = resolved.x.is_some() && resolved.y.is_some();
+        let geographic = resolved.lon.is_some() && resolved.lat.is_some();
+        if !(projected || geographic) {
+            bail!("Address input {:?}: no x/y or lon/lat column pair", input);
+        }
+        let absent: Vec<&str> = [
+            (&self.x, &resolved.x),
+            (&self.y, &resolved.y),
+            (&self.lon, &resolved.lon),
+            (&self.lat, &resolved.lat),
+            (&self.existing_link, &resolved.existing_link),
+            (&self.type_position, &resolved.type_position),
+            (&self.source_position, &resolved.source_position),
+        ]
+        .into_iter()
+        .filter_map(|(mapped, found)| mapped.as_deref().filter(|_| found.is_none()))
+        .collect();
+        if !absent.is_empty() {
+            info!(input=?input, ?absent, "mapped columns absent from the input, staged as null");
+        }
+        Ok(resolved)
+    }
+}
+
+/// Parquet inputs are recognised by extension; anything else is read as CSV.
+pub fn is_parquet(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("parquet"))
+}
+
+/// Step B of one department: `raw_dir` downloads to the `staging_dir` Parquet files. The native
+/// backend reads the `.gz` downloads, DuckDB the decompressed files.
+#[cfg_attr(not(feature = "duckdb-qa"), allow(unused_variables))]
+pub fn prepare_department(
+    dept: &str,
+    raw_dir: &Path,
+    staging_dir: &Path,
+    force: bool,
+    buildings: bool,
+    backend: PrepareBackend,
+    spatial: &SpatialExtension,
+) -> Result<()> {
+    backend.ensure_available()?;
+    let t0 = Instant::now();
+    let crs = department_crs(dept);
+    let layers = [
+        Some((
+            "parcels",
+            format!("cadastre-{}-parcelles.json", dept),
+            "parcelles",
+        )),
+        Some(("addresses", format!("adresses-{}.csv", dept), "adresses")),
+        buildings.then(|| {
+            (
+                "buildings",
+                format!("cadastre-{}-batiments.json", dept),
+                "batiments",
+            )
+        }),
+    ];
+    for (layer, name, staged) in layers.into_iter().flatten() {
+        let output = staging_dir.join(format!("{}_{}.parquet", staged, dept));
+        if !force && output.exists() {
+            continue;
+        }
+        let columns = AddressColumns::default();
+        match backend {
+            #[cfg(feature = "duckdb-qa")]
+            PrepareBackend::Duckdb => {
+                let input = raw_dir.join(&name);
+                match layer {
+                    "parcels" => sql::step_prepare_parcels(&input, &output, &crs, spatial)?,
+                    "addresses" => {
+                        sql::step_prepare_addresses(&input, &output, &crs, &columns, spatial)?
+                    }
+                    _ => sql::step_prepare_buildings(&input, &output, &crs, spatial)?,
+                }
+            }
+            #[cfg(not(feature = "duckdb-qa"))]
+            PrepareBackend::Duckdb => unreachable!("rejected by ensure_available"),
+            PrepareBackend::Native => {
+                let input = raw_dir.join(format!("{}.gz", name));
+                match layer {
+                    "parcels" => native::step_prepare_parcels(&input, &output, &crs)?,
+                    "addresses" => native::step_prepare_addresses(&input, &output, &crs, &columns)?,
+                    _ => native::step_prepare_buildings(&input, &output, &crs)?,
+                }
+            }
+        }
+    }
+    info!(
+        dept=%dept,
+        step="prepare",
+        backend=?backend,
+        crs=crs.code,
+        duration_s=t0.elapsed().as_secs_f32(),
+        "step completed"
+    );
+    Ok(())
+}
+
+/// Prepares the downloaded files of one department (`prepare`), without downloading.
+#[instrument(skip(args))]
+pub fn run_prepare(args: PrepareArgs) -> Result<()> {
+    let raw_dir = args.data_dir.join("raw");
+    let staging_dir = args.data_dir.join("staging");
+    fs::create_dir_all(&staging_dir)?;
+    prepare_department(
+        &args.dept,
+        &raw_dir,
+        &staging_dir,
+        args.force,
+        args.buildings,
+        args.prepare_backend,
+        &SpatialExtension::from_path(args.spatial_extension),
+    )
+}
+
+/// Prepares one point dataset (`prepare-points`) into an address staging file.
+#[instrument(skip(args))]
+pub fn run_prepare_points(args: PreparePointsArgs) -> Result<()> {
+    args.prepare_backend.ensure_available()?;
+    let columns = AddressColumns::load(args.columns.as_deref())?;
+    let crs = department_crs(&args.dept);
+    info!(input=?args.input, output=?args.output, crs=crs.code, ?columns, "starting prepare-points");
+    if args.force && args.output.exists() {
+        fs::remove_file(&args.output)
+            .with_context(|| format!("Failed to remove {:?}", args.output))?;
+    } else if args.output.exists() {
+        warn!(output=?args.output, "output exists; use --force to prepare again");
+        return Ok(());
+    }
+    let t0 = Instant::now();
+    match args.prepare_backend {
+        #[cfg(feature = "duckdb-qa")]
+        PrepareBackend::Duckdb => sql::step_prepare_addresses(
+            &args.input,
+            &args.output,
+            &crs,
+            &columns,
+            &SpatialExtension::from_path(args.spatial_extension),
+        )?,
+        #[cfg(not(feature = "duckdb-qa"))]
+        PrepareBackend::Duckdb => unreachable!("rejected by ensure_available"),
+        PrepareBackend::Native => {
+            native::step_prepare_addresses(&args.input, &args.output, &crs, &columns)?
+        }
+    }
+    info!(
+        backend=?args.prepare_backend,
+        duration_s=t0.elapsed().as_secs_f32(),
+        "prepare-points completed"
+    );
+    Ok(())
+}
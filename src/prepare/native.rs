@@ -0,0 +1,672 @@
+//! Prepare backend without DuckDB: streams the Etalab GeoJSON / BAN CSV (plain or `.gz`) or a
+//! point Parquet, projects CRS84 coordinates in Rust and writes the staging layout read by
+//! `loader`.
+//! Rows are kept and dropped with the same rules as the SQL of `sql`, so both backends
+//! produce the same parcel and address id sets on the same input.
+
+use crate::prepare::{is_parquet, AddressColumns};
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{Array, ArrayRef, BinaryArray, Float64Array, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use ban_cadastre::crs::WorkingCrs;
+use flate2::read::GzDecoder;
+use geo::orient::Direction;
+use geo::{
+    unary_union, Area, Coord, Geometry, LineString, MultiPolygon, Orient, Point, Polygon,
+    RemoveRepeatedPoints, Validation,
+};
+use geozero::{CoordDimensions, ToWkb};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+const BATCH_SIZE: usize = 50_000;
+
+/// Reader over `path`, decompressed when the file name ends in `.gz`.
+pub(crate) fn open_input(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("gz"))
+    {
+        Ok(Box::new(BufReader::new(GzDecoder::new(BufReader::new(
+            file,
+        )))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// GeoJSON
+
+#[derive(Deserialize)]
+struct Feature {
+    #[serde(default)]
+    properties: Option<Properties>,
+    #[serde(default)]
+    geometry: Option<FeatureGeometry>,
+}
+
+#[derive(Deserialize)]
+struct Properties {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    commune: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct FeatureGeometry {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    coordinates: Value,
+}
+
+/// Property rendered as DuckDB `CAST(.. AS VARCHAR)` would (NULL stays None).
+fn property_string(v: Option<Value>) -> Option<String> {
+    match v? {
+        Value::Null => None,
+        Value::String(s) => Some(s),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Calls `on_feature` for each element of `features`, one at a time.
+struct FeatureCollection<'a, F>(&'a mut F);
+
+impl<'de, F: FnMut(Feature) -> Result<()>> DeserializeSeed<'de> for FeatureCollection<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(Feature) -> Result<()>> Visitor<'de> for FeatureCollection<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a GeoJSON FeatureCollection")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "features" {
+                map.next_value_seed(Features(&mut *self.0))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Features<'a, F>(&'a mut F);
+
+impl<'de, F: FnMut(Feature) -> Result<()>> DeserializeSeed<'de> for Features<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(Feature) -> Result<()>> Visitor<'de> for Features<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of GeoJSON features")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(feature) = seq.next_element::<Feature>()? {
+            (self.0)(feature).map_err(de::Error::custom)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_features(input: &Path, mut on_feature: impl FnMut(Feature) -> Result<()>) -> Result<()> {
+    let mut de = serde_json::Deserializer::from_reader(open_input(input)?);
+    FeatureCollection(&mut on_feature)
+        .deserialize(&mut de)
+        .with_context(|| format!("Failed reading GeoJSON {:?}", input))?;
+    de.end()?;
+    Ok(())
+}
+
+/// GeoJSON ring → projected ring (`ST_Force2D` + `ST_Transform`); None on malformed positions.
+fn project_ring(ring: &Value, crs: &WorkingCrs) -> Option<LineString> {
+    ring.as_array()?
+        .iter()
+        .map(|pos| {
+            let pos = pos.as_array()?;
+            let (lon, lat) = (pos.first()?.as_f64()?, pos.get(1)?.as_f64()?);
+            let (x, y) = crs.project(lon, lat);
+            (x.is_finite() && y.is_finite()).then_some(Coord { x, y })
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(LineString::new)
+}
+
+fn project_polygon(rings: &Value, crs: &WorkingCrs) -> Option<Polygon> {
+    let mut rings = rings
+        .as_array()?
+        .iter()
+        .map(|r| project_ring(r, crs))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter();
+    let exterior = rings.next()?;
+    Some(Polygon::new(exterior, rings.collect()))
+}
+
+/// Polygonal part of a repaired geometry, as `ST_CollectionExtract(ST_MakeValid(..), 3)`:
+/// self-intersections are resolved by a union, rings and polygons without area are dropped.
+/// None when nothing polygonal remains (the SQL path deletes those rows too).
+fn clean_geometry(geometry: &FeatureGeometry, crs: &WorkingCrs) -> Option<Geometry> {
+    let polygons: Vec<Polygon> = match geometry.kind.as_str() {
+        "Polygon" => vec![project_polygon(&geometry.coordinates, crs)?],
+        "MultiPolygon" => geometry
+            .coordinates
+            .as_array()?
+            .iter()
+            .map(|p| project_polygon(p, crs))
+            .collect::<Option<Vec<_>>>()?,
+        _ => return None,
+    };
+
+    let has_area = |ring: &LineString| {
+        ring.0.len() >= 4 && Polygon::new(ring.clone(), vec![]).unsigned_area() > 0.0
+    };
+    let polygons: Vec<Polygon> = polygons
+        .into_iter()
+        .map(|p| p.remove_repeated_points())
+        .filter(|p| has_area(p.exterior()))
+        .map(|p| {
+            let (exterior, interiors) = p.into_inner();
+            Polygon::new(exterior, interiors.into_iter().filter(has_area).collect())
+        })
+        .collect();
+
+    let mut cleaned = MultiPolygon::new(polygons);
+    if !cleaned.is_valid() {
+        cleaned = unary_union(&[cleaned.orient(Direction::Default)]);
+    }
+    cleaned.0.retain(|p| p.unsigned_area() > 0.0);
+    match cleaned.0.len() {
+        0 => None,
+        1 => Some(Geometry::Polygon(cleaned.0.remove(0))),
+        _ => Some(Geometry::MultiPolygon(cleaned)),
+    }
+}
+
+pub(crate) fn to_wkb(geometry: &Geometry) -> Result<Vec<u8>> {
+    geometry
+        .to_wkb(CoordDimensions::xy())
+        .map_err(|e| anyhow!("Failed to encode WKB: {}", e))
+}
+
+// ---------------------------------------------------------------------------
+// Staging Parquet
+
+/// Staging Parquet writer: `id`, `code_insee`, `geom` (WKB) then nullable string columns.
+/// Written to `<output>.tmp` and renamed on `finish`, so an interrupted run leaves no
+/// staging file behind.
+pub(crate) struct StagingWriter<'a> {
+    output: &'a Path,
+    tmp: std::path::PathBuf,
+    writer: ArrowWriter<File>,
+    schema: Arc<Schema>,
+    ids: Vec<String>,
+    communes: Vec<String>,
+    geoms: Vec<Vec<u8>>,
+    extras: Vec<Vec<Option<String>>>,
+    rows: usize,
+}
+
+impl<'a> StagingWriter<'a> {
+    pub(crate) fn new(output: &'a Path, extra_columns: &[&str]) -> Result<Self> {
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut fields = vec![
+            Field::new("id", DataType::Utf8, true),
+            Field::new("code_insee", DataType::Utf8, true),
+            Field::new("geom", DataType::Binary, true),
+        ];
+        fields.extend(
+            extra_columns
+                .iter()
+                .map(|c| Field::new(*c, DataType::Utf8, true)),
+        );
+        let schema = Arc::new(Schema::new(fields));
+        let tmp = output.with_extension("parquet.tmp");
+        let file = File::create(&tmp).with_context(|| format!("Failed to create {:?}", tmp))?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(props))
+            .context("Failed to create ArrowWriter")?;
+        Ok(Self {
+            output,
+            tmp,
+            writer,
+            schema,
+            ids: Vec::new(),
+            communes: Vec::new(),
+            geoms: Vec::new(),
+            extras: vec![Vec::new(); extra_columns.len()],
+            rows: 0,
+        })
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        id: String,
+        code_insee: String,
+        wkb: Vec<u8>,
+        extras: Vec<Option<String>>,
+    ) -> Result<()> {
+        self.ids.push(id);
+        self.communes.push(code_insee);
+        self.geoms.push(wkb);
+        for (column, value) in self.extras.iter_mut().zip(extras) {
+            column.push(value);
+        }
+        self.rows += 1;
+        if self.ids.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.ids.is_empty() {
+            return Ok(());
+        }
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(std::mem::take(&mut self.ids))),
+            Arc::new(StringArray::from(std::mem::take(&mut self.communes))),
+            Arc::new(BinaryArray::from_iter_values(self.geoms.drain(..))),
+        ];
+        for column in self.extras.iter_mut() {
+            columns.push(Arc::new(StringArray::from(std::mem::take(column))));
+        }
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&batch)?;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<usize> {
+        self.flush()?;
+        self.writer.close()?;
+        fs::rename(&self.tmp, self.output)
+            .with_context(|| format!("Failed to move staging file to {:?}", self.output))?;
+        Ok(self.rows)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Steps
+
+/// Same rows as `prepare::step_prepare_parcels`: id, commune and a polygonal geometry required.
+pub fn step_prepare_parcels(input: &Path, output_parquet: &Path, crs: &WorkingCrs) -> Result<()> {
+    if output_parquet.exists() {
+        return Ok(());
+    }
+    let mut writer = StagingWriter::new(output_parquet, &[])?;
+    let mut skipped = 0usize;
+    read_features(input, |feature| {
+        let properties = feature.properties;
+        let (Some(id), Some(commune), Some(geom)) = (
+            properties
+                .as_ref()
+                .and_then(|p| property_string(p.id.clone())),
+            properties.and_then(|p| property_string(p.commune)),
+            feature.geometry.and_then(|g| clean_geometry(&g, crs)),
+        ) else {
+            skipped += 1;
+            return Ok(());
+        };
+        writer.push(id, commune, to_wkb(&geom)?, vec![])
+    })?;
+    let written = writer.finish()?;
+    info!(
+        layer = "parcels",
+        written,
+        skipped,
+        crs = crs.code,
+        "native prepare"
+    );
+    Ok(())
+}
+
+/// Buildings get `<commune>-<n>` ids like the SQL path, `n` being the rank of the WKB within the
+/// commune. WKB bytes differ between backends (floating point), so building ids are only
+/// reproducible within one backend.
+pub fn step_prepare_buildings(input: &Path, output_parquet: &Path, crs: &WorkingCrs) -> Result<()> {
+    if output_parquet.exists() {
+        return Ok(());
+    }
+    let mut rows: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut skipped = 0usize;
+    read_features(input, |feature| {
+        let (Some(commune), Some(geom)) = (
+            feature.properties.and_then(|p| property_string(p.commune)),
+            feature.geometry.and_then(|g| clean_geometry(&g, crs)),
+        ) else {
+            skipped += 1;
+            return Ok(());
+        };
+        rows.push((commune, to_wkb(&geom)?));
+        Ok(())
+    })?;
+    rows.sort_unstable();
+
+    let mut writer = StagingWriter::new(output_parquet, &[])?;
+    let mut rank: HashMap<String, usize> = HashMap::new();
+    for (commune, wkb) in rows {
+        let n = rank.entry(commune.clone()).or_insert(0);
+        *n += 1;
+        writer.push(format!("{}-{}", commune, n), commune, wkb, vec![])?;
+    }
+    let written = writer.finish()?;
+    info!(
+        layer = "buildings",
+        written,
+        skipped,
+        crs = crs.code,
+        "native prepare"
+    );
+    Ok(())
+}
+
+/// One address input record, before the row rules (`None`: column unmapped, empty or, for
+/// Parquet coordinates, not numeric).
+struct AddressRecord {
+    id: Option<String>,
+    code_insee: Option<String>,
+    /// x, y, lon, lat
+    coords: [Option<f64>; 4],
+    /// existing_link, type_position, source_position
+    extras: Vec<Option<String>>,
+}
+
+/// `;` or `,` CSV (plain or `.gz`); `on_record(None)` for a record that does not parse.
+fn read_address_csv(
+    input: &Path,
+    columns: &AddressColumns,
+    mut on_record: impl FnMut(Option<AddressRecord>) -> Result<()>,
+) -> Result<()> {
+    // BAN exports use `;`; detect on the header line like DuckDB's sniffer.
+    let mut reader = open_input(input)?;
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    let delimiter = if header.contains(';') { b';' } else { b',' };
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(Cursor::new(header).chain(reader));
+
+    let headers = csv.headers()?.clone();
+    let columns = columns.resolve(input, &headers.iter().collect::<Vec<_>>())?;
+    let column = |name: &Option<String>| {
+        name.as_ref()
+            .and_then(|n| headers.iter().position(|h| h == n))
+    };
+    let (id_col, insee_col) = (
+        column(&Some(columns.id.clone())).unwrap(),
+        column(&Some(columns.code_insee.clone())).unwrap(),
+    );
+    let coord_cols = [&columns.x, &columns.y, &columns.lon, &columns.lat].map(column);
+    let extra_cols = [
+        &columns.existing_link,
+        &columns.type_position,
+        &columns.source_position,
+    ]
+    .map(column);
+
+    for record in csv.records() {
+        let Ok(record) = record else {
+            on_record(None)?;
+            continue;
+        };
+        let field = |col: Option<usize>| {
+            col.and_then(|c| record.get(c))
+                .filter(|v| !v.is_empty())
+                .map(str::to_owned)
+        };
+        let number = |col: Option<usize>| -> Result<Option<f64>, ()> {
+            field(col)
+                .map(|v| v.trim().parse().map_err(|_| ()))
+                .transpose()
+        };
+        let [Ok(x), Ok(y), Ok(lon), Ok(lat)] = coord_cols.map(number) else {
+            on_record(None)?;
+            continue;
+        };
+        on_record(Some(AddressRecord {
+            id: field(Some(id_col)),
+            code_insee: field(Some(insee_col)),
+            coords: [x, y, lon, lat],
+            extras: extra_cols.iter().map(|c| field(*c)).collect(),
+        }))?;
+    }
+    Ok(())
+}
+
+/// Parquet: mapped columns are cast to strings / doubles (a value that does not cast is null).
+fn read_address_parquet(
+    input: &Path,
+    columns: &AddressColumns,
+    mut on_record: impl FnMut(Option<AddressRecord>) -> Result<()>,
+) -> Result<()> {
+    let file = File::open(input).with_context(|| format!("Failed to open {:?}", input))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .context("Failed to create parquet reader")?;
+    let schema = builder.schema().clone();
+    let header: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    let columns = columns.resolve(input, &header)?;
+    for batch in builder.build()? {
+        let batch = batch?;
+        let column = |name: &Option<String>, to: &DataType| -> Result<Option<ArrayRef>> {
+            let Some(name) = name else {
+                return Ok(None);
+            };
+            let col = batch
+                .column_by_name(name)
+                .ok_or_else(|| anyhow!("Address Parquet {:?}: missing column {}", input, name))?;
+            Ok(Some(cast(col, to)?))
+        };
+        let id = column(&Some(columns.id.clone()), &DataType::Utf8)?;
+        let insee = column(&Some(columns.code_insee.clone()), &DataType::Utf8)?;
+        let coords = [&columns.x, &columns.y, &columns.lon, &columns.lat]
+            .map(|c| column(c, &DataType::Float64))
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        let extras = [
+            &columns.existing_link,
+            &columns.type_position,
+            &columns.source_position,
+        ]
+        .map(|c| column(c, &DataType::Utf8))
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+        for i in 0..batch.num_rows() {
+            let text = |a: &Option<ArrayRef>| {
+                let a = a.as_ref()?.as_any().downcast_ref::<StringArray>()?;
+                Some(a.value(i).trim().to_string()).filter(|v| a.is_valid(i) && !v.is_empty())
+            };
+            let number = |a: &Option<ArrayRef>| {
+                let a = a.as_ref()?.as_any().downcast_ref::<Float64Array>()?;
+                a.is_valid(i).then(|| a.value(i))
+            };
+            on_record(Some(AddressRecord {
+                id: text(&id),
+                code_insee: text(&insee),
+                coords: [0, 1, 2, 3].map(|c| number(&coords[c])),
+                extras: extras.iter().map(text).collect(),
+            }))?;
+        }
+    }
+    Ok(())
+}
+
+/// Same rows as `sql::step_prepare_addresses`: `x`/`y` kept as is, else `lon`/`lat`
+/// projected; records that do not parse are skipped (`ignore_errors=true`).
+pub fn step_prepare_addresses(
+    input: &Path,
+    output_parquet: &Path,
+    crs: &WorkingCrs,
+    columns: &AddressColumns,
+) -> Result<()> {
+    if output_parquet.exists() {
+        return Ok(());
+    }
+
+    let mut writer = StagingWriter::new(
+        output_parquet,
+        &["existing_link", "type_position", "source_position"],
+    )?;
+    let (mut skipped, mut malformed) = (0usize, 0usize);
+    let mut on_record = |record: Option<AddressRecord>| -> Result<()> {
+        let Some(record) = record else {
+            malformed += 1;
+            return Ok(());
+        };
+        let point = match record.coords {
+            [Some(x), Some(y), _, _] => Point::new(x, y),
+            [_, _, Some(lon), Some(lat)] => crs.project(lon, lat).into(),
+            _ => {
+                skipped += 1;
+                return Ok(());
+            }
+        };
+        let (Some(id), Some(code_insee)) = (record.id, record.code_insee) else {
+            skipped += 1;
+            return Ok(());
+        };
+        writer.push(
+            id,
+            code_insee,
+            to_wkb(&Geometry::Point(point))?,
+            record.extras,
+        )
+    };
+    if is_parquet(input) {
+        read_address_parquet(input, columns, &mut on_record)?;
+    } else {
+        read_address_csv(input, columns, &mut on_record)?;
+    }
+    let written = writer.finish()?;
+    if malformed > 0 {
+        warn!(malformed, input=?input, "address input: unreadable records skipped");
+    }
+    info!(
+        layer = "addresses",
+        written,
+        skipped,
+        crs = crs.code,
+        "native prepare"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ban_cadastre::crs::LAMBERT_93;
+
+    #[test]
+    fn features_are_streamed_and_repaired() {
+        let square = "[[2.0,46.0],[2.001,46.0],[2.001,46.001],[2.0,46.001],[2.0,46.0]]";
+        let bowtie = "[[2.0,46.0],[2.001,46.001],[2.001,46.0],[2.0,46.001],[2.0,46.0]]";
+        let flat = "[[3.0,46.0],[3.0,46.001],[3.0,46.002],[3.0,46.0]]";
+        let feature = |id: &str, geometry: &str| {
+            format!(
+                r#"{{"type":"Feature","id":"{id}","geometry":{geometry},"properties":{{"id":"{id}","commune":"01001"}}}}"#
+            )
+        };
+        let json = format!(
+            r#"{{"type":"FeatureCollection","features":[{},{},{},{},{}],"name":"test"}}"#,
+            feature(
+                "ok",
+                &format!(r#"{{"type":"Polygon","coordinates":[{square}]}}"#)
+            ),
+            feature(
+                "bowtie",
+                &format!(r#"{{"type":"Polygon","coordinates":[{bowtie}]}}"#)
+            ),
+            feature(
+                "flat",
+                &format!(r#"{{"type":"Polygon","coordinates":[{flat}]}}"#)
+            ),
+            feature("point", r#"{"type":"Point","coordinates":[2.0,46.0]}"#),
+            feature("null", "null"),
+        );
+        let path =
+            std::env::temp_dir().join(format!("ban_cadastre_native_{}.json", std::process::id()));
+        fs::write(&path, json).unwrap();
+
+        let mut kept = Vec::new();
+        read_features(&path, |f| {
+            let id = property_string(f.properties.and_then(|p| p.id)).unwrap();
+            if let Some(g) = f.geometry.and_then(|g| clean_geometry(&g, &LAMBERT_93)) {
+                assert!(g.is_valid(), "{id}");
+                kept.push(id);
+            }
+            Ok(())
+        })
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(kept, ["ok", "bowtie"]);
+    }
+
+    #[test]
+    fn mapped_point_dataset_is_staged_like_ban() {
+        let columns: AddressColumns = serde_json::from_str(
+            r#"{"id": "siret", "code_insee": "commune", "x": "abscisse", "y": "ordonnee",
+                "lon": null, "lat": null, "existing_link": null}"#,
+        )
+        .unwrap();
+        assert_eq!(columns.type_position.as_deref(), Some("type_position"));
+
+        let dir = std::env::temp_dir().join(format!("ban_cadastre_points_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("sirene.csv"), dir.join("points.parquet"));
+        fs::write(
+            &input,
+            "siret,commune,abscisse,ordonnee\n1,01001,700000,6600000\n2,01001,,\n3,01001,x,1\n",
+        )
+        .unwrap();
+        step_prepare_addresses(&input, &output, &LAMBERT_93, &columns).unwrap();
+        let staged = ban_cadastre::loader::load_addresses(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(staged.len(), 1);
+        assert_eq!(
+            (staged[0].id.as_str(), staged[0].code_insee.as_str()),
+            ("1", "01001")
+        );
+        assert_eq!(
+            (staged[0].geom.x(), staged[0].geom.y()),
+            (700_000.0, 6_600_000.0)
+        );
+        assert_eq!(staged[0].existing_link, None);
+    }
+}
@@ -0,0 +1,401 @@
+//! DuckDB prepare backend: `ST_Read` / `read_csv` with the `spatial` extension.
+
+use crate::prepare::{is_parquet, AddressColumns, SpatialExtension};
+use anyhow::{Context, Result};
+use ban_cadastre::crs::WorkingCrs;
+use duckdb::{Config, Connection};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+fn sql_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .replace('\'', "''")
+}
+
+/// `"column"` (quoted identifier) cast to `ty`, or a typed NULL for an unmapped column.
+fn sql_column(column: Option<&str>, ty: &str) -> String {
+    match column {
+        Some(c) => format!("TRY_CAST(\"{}\" AS {})", c.replace('"', "\"\""), ty),
+        None => format!("CAST(NULL AS {})", ty),
+    }
+}
+
+/// A prepare statement rejected by DuckDB.
+#[derive(Debug)]
+pub struct PrepareError {
+    /// Prepared layer: `parcels`, `buildings` or `addresses`.
+    pub layer: &'static str,
+    /// Statement name: `load_spatial`, `read`, `clean`, `drop_empty` or `export`.
+    pub statement: &'static str,
+    pub source: duckdb::Error,
+}
+
+impl fmt::Display for PrepareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "prepare {}: statement {} failed: {}",
+            self.layer, self.statement, self.source
+        )
+    }
+}
+
+impl std::error::Error for PrepareError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Runs the named statements of one layer in a fresh in-memory database with `spatial` loaded.
+fn run_prepare(
+    layer: &'static str,
+    spatial: &SpatialExtension,
+    statements: &[(&'static str, String)],
+) -> Result<()> {
+    let conn = Connection::open_in_memory_with_flags(Config::default())
+        .with_context(|| format!("Failed to open DuckDB for prepare {}", layer))?;
+    let load = match spatial {
+        SpatialExtension::Install => "INSTALL spatial; LOAD spatial;".to_string(),
+        SpatialExtension::Path(path) => format!("LOAD '{}';", sql_path(path)),
+    };
+    let fail = |statement, source| PrepareError {
+        layer,
+        statement,
+        source,
+    };
+    conn.execute_batch(&load)
+        .map_err(|e| fail("load_spatial", e))?;
+    for (statement, sql) in statements {
+        conn.execute_batch(sql).map_err(|e| fail(statement, e))?;
+    }
+    Ok(())
+}
+
+pub fn step_prepare_parcels(
+    input_json: &Path,
+    output_parquet: &Path,
+    crs: &WorkingCrs,
+    spatial: &SpatialExtension,
+) -> Result<()> {
+    if output_parquet.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = output_parquet.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let statements = [
+        (
+            "read",
+            format!(
+                "CREATE OR REPLACE TABLE parcelles_raw AS SELECT * FROM ST_Read('{}');",
+                sql_path(input_json)
+            ),
+        ),
+        (
+            "clean",
+            format!(
+                r#"
+CREATE OR REPLACE TABLE parcelles_clean AS
+SELECT
+  id AS id,
+  CAST(commune AS VARCHAR) AS code_insee,
+  ST_CollectionExtract(
+    ST_MakeValid(
+      ST_Transform(
+        ST_Force2D(geom),
+        'OGC:CRS84',
+        '{crs}'
+      )
+    ),
+    3
+  ) AS geom
+FROM parcelles_raw
+WHERE geom IS NOT NULL
+  AND id IS NOT NULL
+  AND commune IS NOT NULL;
+"#,
+                crs = crs.code,
+            ),
+        ),
+        (
+            "drop_empty",
+            "DELETE FROM parcelles_clean WHERE geom IS NULL OR ST_IsEmpty(geom);".to_string(),
+        ),
+        (
+            "export",
+            format!(
+                r#"
+COPY (
+  SELECT
+    id AS id,
+    code_insee,
+    ST_AsWKB(geom) AS geom
+  FROM parcelles_clean
+) TO '{output}' (FORMAT PARQUET, COMPRESSION 'SNAPPY');
+"#,
+                output = sql_path(output_parquet),
+            ),
+        ),
+    ];
+
+    run_prepare("parcels", spatial, &statements)
+}
+
+/// Buildings carry no id in the Etalab export: `<commune>-<n>` is used instead, `n` being the
+/// rank of the building geometry (WKB order) within its commune, so ids are reproducible.
+pub fn step_prepare_buildings(
+    input_json: &Path,
+    output_parquet: &Path,
+    crs: &WorkingCrs,
+    spatial: &SpatialExtension,
+) -> Result<()> {
+    if output_parquet.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = output_parquet.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let statements = [
+        (
+            "read",
+            format!(
+                "CREATE OR REPLACE TABLE batiments_raw AS SELECT * FROM ST_Read('{}');",
+                sql_path(input_json)
+            ),
+        ),
+        (
+            "clean",
+            format!(
+                r#"
+CREATE OR REPLACE TABLE batiments_clean AS
+SELECT
+  CAST(commune AS VARCHAR) || '-' || CAST(
+    row_number() OVER (PARTITION BY commune ORDER BY ST_AsWKB(geom)) AS VARCHAR
+  ) AS id,
+  CAST(commune AS VARCHAR) AS code_insee,
+  ST_CollectionExtract(
+    ST_MakeValid(
+      ST_Transform(
+        ST_Force2D(geom),
+        'OGC:CRS84',
+        '{crs}'
+      )
+    ),
+    3
+  ) AS geom
+FROM batiments_raw
+WHERE geom IS NOT NULL
+  AND commune IS NOT NULL;
+"#,
+                crs = crs.code,
+            ),
+        ),
+        (
+            "drop_empty",
+            "DELETE FROM batiments_clean WHERE geom IS NULL OR ST_IsEmpty(geom);".to_string(),
+        ),
+        (
+            "export",
+            format!(
+                r#"
+COPY (
+  SELECT
+    id,
+    code_insee,
+    ST_AsWKB(geom) AS geom
+  FROM batiments_clean
+) TO '{output}' (FORMAT PARQUET, COMPRESSION 'SNAPPY');
+"#,
+                output = sql_path(output_parquet),
+            ),
+        ),
+    ];
+
+    run_prepare("buildings", spatial, &statements)
+}
+
+/// DuckDB table function reading an address input (CSV or Parquet).
+fn address_reader(input: &Path) -> String {
+    if is_parquet(input) {
+        format!("read_parquet('{}')", sql_path(input))
+    } else {
+        format!(
+            "read_csv('{}', auto_detect=true, header=true, ignore_errors=true)",
+            sql_path(input)
+        )
+    }
+}
+
+/// `columns` resolved against the input header as DuckDB reads it (no `spatial` needed).
+fn resolve_address_columns(input: &Path, columns: &AddressColumns) -> Result<AddressColumns> {
+    let conn = Connection::open_in_memory_with_flags(Config::default())
+        .context("Failed to open DuckDB for prepare addresses")?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT column_name FROM (DESCRIBE SELECT * FROM {})",
+        address_reader(input)
+    ))?;
+    let header = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read the header of {:?}", input))?;
+    columns.resolve(
+        input,
+        &header.iter().map(String::as_str).collect::<Vec<_>>(),
+    )
+}
+
+/// Statements of `step_prepare_addresses`, `columns` being resolved against the input.
+fn address_statements(
+    input: &Path,
+    output_parquet: &Path,
+    crs: &WorkingCrs,
+    columns: &AddressColumns,
+) -> [(&'static str, String); 3] {
+    let reader = address_reader(input);
+    let text = |c: &Option<String>| sql_column(c.as_deref(), "VARCHAR");
+    let number = |c: &Option<String>| sql_column(c.as_deref(), "DOUBLE");
+    [
+        (
+            "read",
+            format!(
+                r#"
+CREATE OR REPLACE TABLE adresses_raw AS
+SELECT
+  {id} AS id, {code_insee} AS code_insee,
+  {x} AS x, {y} AS y, {lon} AS lon, {lat} AS lat,
+  {link} AS cad_parcelles, {type_position} AS type_position, {source_position} AS source_position
+FROM {reader};
+"#,
+                id = sql_column(Some(&columns.id), "VARCHAR"),
+                code_insee = sql_column(Some(&columns.code_insee), "VARCHAR"),
+                x = number(&columns.x),
+                y = number(&columns.y),
+                lon = number(&columns.lon),
+                lat = number(&columns.lat),
+                link = text(&columns.existing_link),
+                type_position = text(&columns.type_position),
+                source_position = text(&columns.source_position),
+            ),
+        ),
+        (
+            "clean",
+            format!(
+                r#"
+CREATE OR REPLACE TABLE adresses_clean AS
+SELECT
+  CAST(id AS VARCHAR)         AS id,
+  CAST(code_insee AS VARCHAR) AS code_insee,
+  CASE
+    WHEN x   IS NOT NULL AND y   IS NOT NULL THEN ST_Point(x, y)
+    WHEN lon IS NOT NULL AND lat IS NOT NULL THEN ST_Transform(ST_Point(lon, lat), 'OGC:CRS84', '{crs}')
+    ELSE NULL
+  END AS geom,
+  CASE
+    WHEN cad_parcelles IS NULL OR cad_parcelles = '' THEN NULL
+    ELSE cad_parcelles
+  END AS existing_link,
+  NULLIF(CAST(type_position AS VARCHAR), '')   AS type_position,
+  NULLIF(CAST(source_position AS VARCHAR), '') AS source_position
+FROM adresses_raw
+WHERE (
+    (x IS NOT NULL AND y IS NOT NULL) OR
+    (lon IS NOT NULL AND lat IS NOT NULL)
+)
+AND id IS NOT NULL
+AND code_insee IS NOT NULL;
+"#,
+                crs = crs.code,
+            ),
+        ),
+        (
+            // Export WKB to match Rust loader expectations.
+            "export",
+            format!(
+                r#"
+COPY (
+  SELECT
+    id,
+    code_insee,
+    ST_AsWKB(geom) AS geom,
+    existing_link,
+    type_position,
+    source_position
+  FROM adresses_clean
+  WHERE geom IS NOT NULL
+) TO '{output}' (FORMAT PARQUET, COMPRESSION 'SNAPPY');
+"#,
+                output = sql_path(output_parquet),
+            ),
+        ),
+    ]
+}
+
+/// Columns come from `columns` (BAN by default): `x`/`y` are already in the legal projection of
+/// the department (`crs`); `lon`/`lat` are reprojected. Optional columns missing from the input
+/// are staged as null (`AddressColumns::resolve`).
+pub fn step_prepare_addresses(
+    input: &Path,
+    output_parquet: &Path,
+    crs: &WorkingCrs,
+    columns: &AddressColumns,
+    spatial: &SpatialExtension,
+) -> Result<()> {
+    if output_parquet.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = output_parquet.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let columns = resolve_address_columns(input, columns)?;
+    let statements = address_statements(input, output_parquet, crs, &columns);
+    run_prepare("addresses", spatial, &statements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ban_cadastre::crs::LAMBERT_93;
+
+    #[test]
+    fn absent_optional_columns_read_as_null() {
+        let columns: AddressColumns = serde_json::from_str(
+            r#"{"id": "siret", "code_insee": "commune", "x": "abscisse", "y": "ordonnee"}"#,
+        )
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!("ban_cadastre_sql_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("sirene.csv");
+        fs::write(
+            &input,
+            "siret,commune,abscisse,ordonnee\n1,01001,700000,6600000\n",
+        )
+        .unwrap();
+
+        let resolved = resolve_address_columns(&input, &columns).unwrap();
+        assert_eq!(resolved.lon, None);
+        assert_eq!(resolved.type_position, None);
+        // `read` binds without `spatial`; the later statements need the extension.
+        let [(_, read), ..] =
+            address_statements(&input, &dir.join("out.parquet"), &LAMBERT_93, &resolved);
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&read).unwrap();
+        let row: (String, f64, Option<String>) = conn
+            .query_row("SELECT id, x, type_position FROM adresses_raw", [], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(row, ("1".to_string(), 700_000.0, None));
+
+        let missing: AddressColumns = serde_json::from_str(r#"{"id": "siret"}"#).unwrap();
+        let err = resolve_address_columns(&input, &missing).unwrap_err();
+        assert!(
+            err.to_string().contains("missing column code_insee"),
+            "{err}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
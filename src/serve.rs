@@ -0,0 +1,402 @@
+use crate::cli::ServeArgs;
+use anyhow::{anyhow, Context, Result};
+use ban_cadastre::confidence::load_scorer;
+use ban_cadastre::crs::{department_crs, WorkingCrs};
+use ban_cadastre::loader::{
+    load_addresses, load_parcels, normalize_address_communes, normalize_parcel_communes,
+};
+use ban_cadastre::structures::{AddressInput, MatchConfig, MatchOutput, ParcelData};
+use ban_cadastre::Matcher;
+use geo::Point;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// One loaded department: matcher session (indexes) and the rows of its 3-step matching.
+struct Department<'a> {
+    dept: String,
+    crs: WorkingCrs,
+    matcher: Matcher<'a>,
+    rows: Vec<MatchOutput>,
+    by_address: HashMap<String, Vec<usize>>,
+    by_parcel: HashMap<String, Vec<usize>>,
+}
+
+impl<'a> Department<'a> {
+    fn build(
+        dept: &str,
+        parcels: &'a Vec<ParcelData>,
+        addresses: &'a [AddressInput],
+        config: MatchConfig,
+    ) -> Result<Self> {
+        let matcher = Matcher::new(parcels, addresses, config)?;
+        let rows = matcher.match_all();
+        let mut by_address: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut by_parcel: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, m) in rows.iter().enumerate() {
+            by_address.entry(m.id_ban.clone()).or_default().push(i);
+            if let Some(pid) = &m.id_parcelle {
+                by_parcel.entry(pid.clone()).or_default().push(i);
+            }
+        }
+        Ok(Self {
+            dept: dept.to_string(),
+            crs: department_crs(dept),
+            matcher,
+            rows,
+            by_address,
+            by_parcel,
+        })
+    }
+
+    fn rows_of(&self, index: &HashMap<String, Vec<usize>>, id: &str) -> Vec<&MatchOutput> {
+        index
+            .get(id)
+            .map(|ids| ids.iter().map(|&i| &self.rows[i]).collect())
+            .unwrap_or_default()
+    }
+}
+
+pub fn run_serve(args: ServeArgs) -> Result<()> {
+    info!(staging_dir=?args.staging_dir, departments=?args.departments, "starting serve mode");
+
+    let mut config = MatchConfig {
+        address_max_distance_m: args.distance_threshold,
+        preexisting_max_distance_m: args.preexisting_max_distance_m,
+        confidence_scorer: load_scorer(args.confidence_model.as_deref())?,
+        ..MatchConfig::default()
+    };
+    args.cross_commune.apply(&mut config);
+    args.positions.apply(&mut config);
+    let cog = args.cog.load()?;
+
+    let start_load = Instant::now();
+    let mut inputs: Vec<(String, Vec<ParcelData>, Vec<AddressInput>)> = Vec::new();
+    for dept in &args.departments {
+        let dept = dept.trim();
+        let mut parcels =
+            load_parcels(&args.staging_dir.join(format!("parcelles_{}.parquet", dept)))?;
+        let mut addresses =
+            load_addresses(&args.staging_dir.join(format!("adresses_{}.parquet", dept)))?;
+        if let Some(cog) = &cog {
+            normalize_parcel_communes(&mut parcels, cog);
+            normalize_address_communes(&mut addresses, cog);
+        }
+        inputs.push((dept.to_string(), parcels, addresses));
+    }
+    let departments: Vec<Department> = inputs
+        .iter()
+        .map(|(dept, parcels, addresses)| {
+            Department::build(dept, parcels, addresses, config.clone())
+        })
+        .collect::<Result<_>>()?;
+    for d in &departments {
+        info!(dept=%d.dept, crs=d.crs.code, rows=d.rows.len(), "department ready");
+    }
+    info!(
+        elapsed_ms = start_load.elapsed().as_millis(),
+        "indexes built"
+    );
+
+    let listener =
+        TcpListener::bind(&args.bind).with_context(|| format!("Failed to bind {}", args.bind))?;
+    info!(bind=%args.bind, workers=args.workers, "listening");
+    std::thread::scope(|s| {
+        for _ in 0..args.workers.max(1) {
+            s.spawn(|| loop {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = handle(stream, &departments) {
+                            warn!(error=%e, "request failed");
+                        }
+                    }
+                    Err(e) => warn!(error=%e, "accept failed"),
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// One HTTP/1.1 GET per connection (`Connection: close`), JSON response.
+fn handle(mut stream: TcpStream, departments: &[Department]) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let (status, body) = respond(departments, &request_line);
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Method Not Allowed",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Status and JSON body of a request line (`GET <target> HTTP/1.1`).
+fn respond(departments: &[Department], request_line: &str) -> (u16, Value) {
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => route(departments, target),
+        _ => (405, json!({ "error": "only GET is supported" })),
+    }
+}
+
+/// `GET /locate?lon=&lat=` (or `x=&y=[&crs=EPSG:2154]`, `[&max_distance_m=]`),
+/// `GET /address?id=`, `GET /parcel?id=`.
+fn route(departments: &[Department], target: &str) -> (u16, Value) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params: HashMap<String, String> = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let result = match path.trim_end_matches('/') {
+        "/locate" => locate(departments, &params),
+        "/address" => lookup_address(departments, &params),
+        "/parcel" => lookup_parcel(departments, &params),
+        _ => Err((404, anyhow!("unknown endpoint {}", path))),
+    };
+    match result {
+        Ok(body) => (200, body),
+        Err((status, e)) => (status, json!({ "error": e.to_string() })),
+    }
+}
+
+type RouteResult = std::result::Result<Value, (u16, anyhow::Error)>;
+
+fn param<'p>(params: &'p HashMap<String, String>, name: &str) -> Option<&'p str> {
+    params.get(name).map(String::as_str)
+}
+
+fn number(
+    params: &HashMap<String, String>,
+    name: &str,
+) -> std::result::Result<Option<f64>, (u16, anyhow::Error)> {
+    param(params, name)
+        .map(|v| {
+            v.parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| (400, anyhow!("{} is not a number: {}", name, v)))
+        })
+        .transpose()
+}
+
+fn locate(departments: &[Department], params: &HashMap<String, String>) -> RouteResult {
+    let max_distance_m = number(params, "max_distance_m")?.unwrap_or(f64::INFINITY);
+    // (department, point in its working CRS)
+    let points: Vec<(&Department, Point<f64>)> = match (
+        number(params, "lon")?,
+        number(params, "lat")?,
+        number(params, "x")?,
+        number(params, "y")?,
+    ) {
+        (Some(lon), Some(lat), _, _) => departments
+            .iter()
+            .map(|d| (d, d.crs.project(lon, lat).into()))
+            .collect(),
+        (_, _, Some(x), Some(y)) => {
+            let crs = param(params, "crs").unwrap_or("EPSG:2154");
+            departments
+                .iter()
+                .filter(|d| d.crs.code.eq_ignore_ascii_case(crs))
+                .map(|d| (d, Point::new(x, y)))
+                .collect()
+        }
+        _ => return Err((400, anyhow!("expected lon & lat, or x & y"))),
+    };
+
+    let best = points
+        .iter()
+        .filter_map(|(d, p)| {
+            let (parcel, distance) = match d.matcher.parcel_at(p) {
+                Some(parcel) => (parcel, 0.0),
+                None => d.matcher.nearest_parcel(p)?,
+            };
+            Some((d, p, parcel, distance))
+        })
+        .min_by(|a, b| a.3.total_cmp(&b.3))
+        .filter(|best| best.3 <= max_distance_m);
+    let Some((d, p, parcel, distance)) = best else {
+        return Err((404, anyhow!("no parcel within {} m", max_distance_m)));
+    };
+    let nearest_address = d
+        .matcher
+        .nearest_address(p)
+        .map(|(a, ad)| json!({ "id_ban": a.id, "distance_m": ad }));
+    Ok(json!({
+        "department": d.dept,
+        "crs": d.crs.code,
+        "x": p.x(),
+        "y": p.y(),
+        "id_parcelle": parcel.id,
+        "code_insee": parcel.code_insee,
+        "inside": distance == 0.0,
+        "distance_m": distance,
+        "nearest_address": nearest_address,
+    }))
+}
+
+fn lookup_address(departments: &[Department], params: &HashMap<String, String>) -> RouteResult {
+    let id = param(params, "id").ok_or_else(|| (400, anyhow!("missing id")))?;
+    let d = departments
+        .iter()
+        .find(|d| d.matcher.address(id).is_some())
+        .ok_or_else(|| (404, anyhow!("unknown address {}", id)))?;
+    Ok(json!({
+        "id_ban": id,
+        "department": d.dept,
+        "links": d.rows_of(&d.by_address, id),
+    }))
+}
+
+fn lookup_parcel(departments: &[Department], params: &HashMap<String, String>) -> RouteResult {
+    let id = param(params, "id").ok_or_else(|| (400, anyhow!("missing id")))?;
+    let (d, parcel) = departments
+        .iter()
+        .find_map(|d| Some((d, d.matcher.parcel(id)?)))
+        .ok_or_else(|| (404, anyhow!("unknown parcel {}", id)))?;
+    Ok(json!({
+        "id_parcelle": id,
+        "code_insee": parcel.code_insee,
+        "department": d.dept,
+        "addresses": d.rows_of(&d.by_parcel, id),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{address, square, LAMBERT_93_ORIGIN};
+    use ban_cadastre::crs::LAMBERT_93;
+
+    const PARCEL: &str = "01001000AB0001";
+    const ADDRESS: &str = "01001_0001_00001";
+
+    /// 10 m square centred on the Lambert-93 origin, with one address at its centre.
+    fn fixture() -> (Vec<ParcelData>, Vec<AddressInput>) {
+        let (x0, y0) = LAMBERT_93_ORIGIN;
+        (
+            vec![square(PARCEL, "01001", x0 - 5.0, y0 - 5.0, 10.0)],
+            vec![address(ADDRESS, "01001", x0, y0)],
+        )
+    }
+
+    fn department<'a>(
+        parcels: &'a Vec<ParcelData>,
+        addresses: &'a [AddressInput],
+    ) -> Department<'a> {
+        Department::build("01", parcels, addresses, MatchConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn locate_by_lon_lat_finds_the_containing_parcel() {
+        let (parcels, addresses) = fixture();
+        let departments = [department(&parcels, &addresses)];
+        assert_eq!(departments[0].crs, LAMBERT_93);
+
+        let (status, body) = route(&departments, "/locate?lon=3.0&lat=46.5");
+        assert_eq!(status, 200);
+        assert_eq!(body["id_parcelle"], PARCEL);
+        assert_eq!(body["inside"], true);
+        assert_eq!(body["nearest_address"]["id_ban"], ADDRESS);
+    }
+
+    #[test]
+    fn locate_by_projected_xy_reports_the_distance() {
+        let (parcels, addresses) = fixture();
+        let departments = [department(&parcels, &addresses)];
+
+        let (status, body) = route(&departments, "/locate?x=700008&y=6600000&crs=EPSG:2154");
+        assert_eq!(status, 200);
+        assert_eq!(
+            (body["inside"].clone(), body["distance_m"].clone()),
+            (json!(false), json!(3.0))
+        );
+        // Projected coordinates only match departments of the same CRS.
+        assert_eq!(
+            route(&departments, "/locate?x=700008&y=6600000&crs=EPSG:2975").0,
+            404
+        );
+    }
+
+    #[test]
+    fn locate_beyond_max_distance_is_not_found() {
+        let (parcels, addresses) = fixture();
+        let departments = [department(&parcels, &addresses)];
+        let (status, body) = route(&departments, "/locate?x=700008&y=6600000&max_distance_m=1");
+        assert_eq!(status, 404);
+        assert!(body["error"].as_str().unwrap().contains("within 1 m"));
+    }
+
+    #[test]
+    fn address_and_parcel_lookups_return_the_links() {
+        let (parcels, addresses) = fixture();
+        let departments = [department(&parcels, &addresses)];
+
+        let (status, body) = route(&departments, &format!("/address?id={}", ADDRESS));
+        assert_eq!(status, 200);
+        assert_eq!(body["links"][0]["id_parcelle"], PARCEL);
+        assert_eq!(body["links"][0]["match_type"], "Inside");
+        let (status, body) = route(&departments, &format!("/parcel?id={}", PARCEL));
+        assert_eq!(status, 200);
+        assert_eq!(body["addresses"][0]["id_ban"], ADDRESS);
+    }
+
+    #[test]
+    fn unknown_ids_and_endpoints_are_not_found() {
+        let (parcels, addresses) = fixture();
+        let departments = [department(&parcels, &addresses)];
+        assert_eq!(route(&departments, "/address?id=nope").0, 404);
+        assert_eq!(route(&departments, "/parcel?id=nope").0, 404);
+        assert_eq!(route(&departments, "/nope").0, 404);
+    }
+
+    #[test]
+    fn malformed_queries_are_bad_requests() {
+        let (parcels, addresses) = fixture();
+        let departments = [department(&parcels, &addresses)];
+        assert_eq!(route(&departments, "/locate?lon=abc&lat=1").0, 400);
+        assert_eq!(route(&departments, "/locate?lon=3.0").0, 400);
+        assert_eq!(
+            route(&departments, "/locate?lon=3.0&lat=46.5&max_distance_m=inf").0,
+            400
+        );
+        assert_eq!(route(&departments, "/address").0, 400);
+    }
+
+    #[test]
+    fn only_get_is_allowed() {
+        let (parcels, addresses) = fixture();
+        let departments = [department(&parcels, &addresses)];
+        assert_eq!(
+            respond(&departments, "GET /parcel?id=01001000AB0001 HTTP/1.1").0,
+            200
+        );
+        assert_eq!(
+            respond(&departments, "POST /parcel?id=01001000AB0001 HTTP/1.1").0,
+            405
+        );
+        assert_eq!(respond(&departments, "").0, 405);
+    }
+}
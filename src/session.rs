@@ -0,0 +1,150 @@
+use crate::error::Error;
+use crate::indexer::{AddressIndex, DepartmentIndex};
+use crate::matcher::{is_inside_or_on_border, match_indexed, nearest_parcel};
+use crate::structures::{
+    AddressInput, MatchConfig, MatchConfigBuilder, MatchOutput, ParcelData, ParcelStore,
+};
+use geo::Point;
+use std::collections::HashMap;
+
+/// Matching session over parcels and addresses of one scope (coordinates in its working CRS).
+/// The parcel and address R-trees are built once by `new`; `match_all` and the point queries
+/// reuse them.
+pub struct Matcher<'a> {
+    config: MatchConfig,
+    parcel_index: DepartmentIndex<'a>,
+    address_index: AddressIndex<'a>,
+    parcels_by_id: HashMap<&'a str, &'a ParcelData>,
+    addresses_by_id: HashMap<&'a str, &'a AddressInput>,
+    buildings: Option<&'a dyn ParcelStore>,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(
+        parcels: &'a dyn ParcelStore,
+        addresses: &'a [AddressInput],
+        config: MatchConfig,
+    ) -> Result<Self, Error> {
+        // Configs built by hand (pub fields) get the builder checks too.
+        let config = MatchConfigBuilder::from(config).build()?;
+        Ok(Self {
+            config,
+            parcel_index: DepartmentIndex::build(parcels),
+            address_index: AddressIndex::build(addresses),
+            parcels_by_id: parcels.iter().map(|p| (p.id.as_str(), p)).collect(),
+            addresses_by_id: addresses.iter().map(|a| (a.id.as_str(), a)).collect(),
+            buildings: None,
+        })
+    }
+
+    /// Cadastre buildings, used when `MatchConfig::prefer_built_parcels` is set.
+    pub fn with_buildings(mut self, buildings: &'a dyn ParcelStore) -> Self {
+        self.buildings = Some(buildings);
+        self
+    }
+
+    pub fn config(&self) -> &MatchConfig {
+        &self.config
+    }
+
+    /// The 3-step matching of all session addresses (same rows as
+    /// `match_parcels_and_addresses_3_steps`).
+    pub fn match_all(&self) -> Vec<MatchOutput> {
+        match_indexed(
+            &self.parcel_index,
+            &self.address_index,
+            self.buildings,
+            &self.config,
+        )
+    }
+
+    pub fn parcel(&self, id: &str) -> Option<&'a ParcelData> {
+        self.parcels_by_id.get(id).copied()
+    }
+
+    pub fn address(&self, id: &str) -> Option<&'a AddressInput> {
+        self.addresses_by_id.get(id).copied()
+    }
+
+    /// Parcel containing `p` (border included).
+    pub fn parcel_at(&self, p: &Point<f64>) -> Option<&'a ParcelData> {
+        self.parcel_index
+            .tree
+            .locate_all_at_point(&[p.x(), p.y()])
+            .map(|node| self.parcel_index.get_parcel(node.idx))
+            .find(|parcel| is_inside_or_on_border(parcel, p))
+    }
+
+    /// Nearest parcel and its point→polygon distance (0 inside), without distance limit.
+    pub fn nearest_parcel(&self, p: &Point<f64>) -> Option<(&'a ParcelData, f64)> {
+        nearest_parcel(&self.parcel_index, p)
+    }
+
+    /// Nearest address and its distance, without distance limit.
+    pub fn nearest_address(&self, p: &Point<f64>) -> Option<(&'a AddressInput, f64)> {
+        self.address_index.nearest(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::MatchType;
+    use crate::test_support::{address, square};
+    use geo::point;
+
+    fn fixture() -> (Vec<ParcelData>, Vec<AddressInput>) {
+        let parcels = vec![
+            square("A", "01001", 0.0, 0.0, 10.0),
+            square("B", "01001", 20.0, 0.0, 10.0),
+        ];
+        let addresses = vec![
+            address("a1", "01001", 5.0, 5.0),
+            address("a2", "01001", 32.0, 5.0),
+        ];
+        (parcels, addresses)
+    }
+
+    #[test]
+    fn match_all_links_inside_and_border_near() {
+        let (parcels, addresses) = fixture();
+        let config = MatchConfig::builder()
+            .address_max_distance_m(5.0)
+            .build()
+            .unwrap();
+        let matcher = Matcher::new(&parcels, &addresses, config).unwrap();
+
+        let rows = matcher.match_all();
+        let link = |id: &str| rows.iter().find(|m| m.id_ban == id).unwrap();
+        assert_eq!(link("a1").match_type, MatchType::Inside);
+        assert_eq!(link("a2").match_type, MatchType::BorderNear);
+        assert_eq!(link("a2").id_parcelle.as_deref(), Some("B"));
+        // Repeated queries reuse the indexes.
+        assert_eq!(matcher.match_all().len(), rows.len());
+    }
+
+    #[test]
+    fn point_queries_answer_parcel_and_address() {
+        let (parcels, addresses) = fixture();
+        let matcher = Matcher::new(&parcels, &addresses, MatchConfig::default()).unwrap();
+
+        assert_eq!(matcher.parcel_at(&point!(x: 25.0, y: 1.0)).unwrap().id, "B");
+        assert!(matcher.parcel_at(&point!(x: 15.0, y: 1.0)).is_none());
+        let (nearest, d) = matcher.nearest_parcel(&point!(x: 14.0, y: 5.0)).unwrap();
+        assert_eq!((nearest.id.as_str(), d), ("A", 4.0));
+        let (addr, d) = matcher.nearest_address(&point!(x: 29.0, y: 1.0)).unwrap();
+        assert_eq!((addr.id.as_str(), d), ("a2", 5.0));
+    }
+
+    #[test]
+    fn invalid_config_and_coordinates_are_rejected() {
+        assert!(matches!(
+            MatchConfigBuilder::default().border_candidates(0).build(),
+            Err(Error::InvalidConfig {
+                field: "border_candidates",
+                ..
+            })
+        ));
+        assert!(AddressInput::new("bad", "01001", point!(x: f64::NAN, y: 0.0)).is_err());
+    }
+}
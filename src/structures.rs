@@ -1,8 +1,11 @@
+use crate::confidence::ConfidenceScorer;
+use crate::error::Error;
 use geo::prelude::*;
 use geo::{MultiPolygon, Point, Polygon};
 use rstar::AABB;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::Arc;
 
 /// Data representation of a Cadastral Parcel.
 #[derive(Debug, Clone)]
@@ -10,16 +13,57 @@ pub struct ParcelData {
     pub id: String,
     pub code_insee: String,
     pub geom: ParcelGeometry,
-    /// Precomputed bounding box in the working CRS (EPSG:2154).
+    /// Precomputed bounding box in the working CRS (`crs::department_crs`).
     pub envelope: AABB<[f64; 2]>,
 }
 
+impl ParcelData {
+    /// Parcel from a polygon or multipolygon in the working CRS; the envelope is computed here.
+    pub fn new(
+        id: impl Into<String>,
+        code_insee: impl Into<String>,
+        geom: impl Into<ParcelGeometry>,
+    ) -> Result<Self, Error> {
+        let id = id.into();
+        let geom = geom.into();
+        let Some(envelope) = geom.envelope_opt() else {
+            return Err(Error::EmptyGeometry { id });
+        };
+        if !envelope
+            .lower()
+            .iter()
+            .chain(envelope.upper().iter())
+            .all(|v| v.is_finite())
+        {
+            return Err(Error::NonFiniteCoordinate { id });
+        }
+        Ok(Self {
+            id,
+            code_insee: code_insee.into(),
+            geom,
+            envelope,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ParcelGeometry {
     Polygon(Polygon<f64>),
     MultiPolygon(MultiPolygon<f64>),
 }
 
+impl From<Polygon<f64>> for ParcelGeometry {
+    fn from(p: Polygon<f64>) -> Self {
+        ParcelGeometry::Polygon(p)
+    }
+}
+
+impl From<MultiPolygon<f64>> for ParcelGeometry {
+    fn from(mp: MultiPolygon<f64>) -> Self {
+        ParcelGeometry::MultiPolygon(mp)
+    }
+}
+
 impl ParcelGeometry {
     pub fn distance_to_point(&self, p: &Point<f64>) -> f64 {
         match self {
@@ -28,6 +72,23 @@ impl ParcelGeometry {
         }
     }
 
+    pub fn area_m2(&self) -> f64 {
+        use geo::Area;
+
+        match self {
+            ParcelGeometry::Polygon(poly) => poly.unsigned_area(),
+            ParcelGeometry::MultiPolygon(mpoly) => mpoly.unsigned_area(),
+        }
+    }
+
+    pub fn interior_point(&self) -> Option<Point<f64>> {
+        use geo::InteriorPoint;
+
+        match self {
+            ParcelGeometry::Polygon(poly) => poly.interior_point(),
+            ParcelGeometry::MultiPolygon(mpoly) => mpoly.interior_point(),
+        }
+    }
 
     /// Returns None if geometry has no bounding rect (empty/invalid).
     pub fn envelope_opt(&self) -> Option<AABB<[f64; 2]>> {
@@ -48,6 +109,9 @@ impl ParcelGeometry {
 pub trait ParcelStore: Sync + Send {
     fn get_parcel(&self, idx: usize) -> &ParcelData;
     fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     fn iter(&self) -> Box<dyn Iterator<Item = &ParcelData> + '_>;
 }
 
@@ -69,6 +133,61 @@ pub struct AddressInput {
     pub code_insee: String,
     pub geom: Point<f64>,
     pub existing_link: Option<String>,
+    /// BAN `type_position` (entrée, bâtiment, parcelle, segment, ...).
+    pub type_position: Option<String>,
+    /// BAN `source_position` (commune, cadastre, arcep, laposte, insee, sdis, inconnue).
+    pub source_position: Option<String>,
+}
+
+impl AddressInput {
+    /// Address point in the working CRS, without BAN link nor position metadata.
+    pub fn new(
+        id: impl Into<String>,
+        code_insee: impl Into<String>,
+        geom: Point<f64>,
+    ) -> Result<Self, Error> {
+        let id = id.into();
+        if !(geom.x().is_finite() && geom.y().is_finite()) {
+            return Err(Error::NonFiniteCoordinate { id });
+        }
+        Ok(Self {
+            id,
+            code_insee: code_insee.into(),
+            geom,
+            existing_link: None,
+            type_position: None,
+            source_position: None,
+        })
+    }
+
+    /// BAN `cad_parcelles` value (one or more parcel ids, see `matcher::split_links`).
+    pub fn with_existing_link(mut self, link: impl Into<String>) -> Self {
+        self.existing_link = Some(link.into());
+        self
+    }
+
+    /// BAN `type_position` / `source_position`.
+    pub fn with_position(
+        mut self,
+        type_position: Option<String>,
+        source_position: Option<String>,
+    ) -> Self {
+        self.type_position = type_position;
+        self.source_position = source_position;
+        self
+    }
+
+    /// `type_position` or `source_position` listed in `MatchConfig::low_precision_positions`.
+    pub fn is_low_precision(&self, low_precision_positions: &[String]) -> bool {
+        [&self.type_position, &self.source_position]
+            .into_iter()
+            .flatten()
+            .any(|v| {
+                low_precision_positions
+                    .iter()
+                    .any(|p| p.eq_ignore_ascii_case(v))
+            })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -77,6 +196,8 @@ pub enum MatchType {
     Inside,
     BorderNear,
     FallbackNearest,
+    /// BAN `cad_parcelles` link whose parcel lies beyond `preexisting_max_distance_m`.
+    PreExistingFar,
     None,
 }
 
@@ -87,9 +208,22 @@ impl MatchType {
             MatchType::Inside => "Inside",
             MatchType::BorderNear => "BorderNear",
             MatchType::FallbackNearest => "FallbackNearest",
+            MatchType::PreExistingFar => "PreExistingFar",
             MatchType::None => "None",
         }
     }
+
+    /// Ranking used for best-per-parcel / best-per-address (same order as the QA SQL).
+    pub const fn priority(&self) -> u32 {
+        match self {
+            MatchType::PreExisting => 0,
+            MatchType::Inside => 1,
+            MatchType::BorderNear => 2,
+            MatchType::FallbackNearest => 3,
+            MatchType::PreExistingFar => 4,
+            MatchType::None => 100,
+        }
+    }
 }
 
 impl fmt::Display for MatchType {
@@ -98,6 +232,11 @@ impl fmt::Display for MatchType {
     }
 }
 
+/// Margins at or above this value (meters) are considered unambiguous.
+pub const AMBIGUITY_MARGIN_CAP_M: f64 = 5.0;
+/// Confidence removed for a zero margin (scaled down linearly up to the cap).
+const AMBIGUITY_MAX_PENALTY: f64 = 20.0;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MatchOutput {
     pub id_ban: String,
@@ -105,6 +244,22 @@ pub struct MatchOutput {
     pub match_type: MatchType,
     pub distance_m: f32,
     pub confidence: u32,
+    /// Nearest parcel for `None` rows (address completion), as a hint only.
+    pub hint_parcelle: Option<String>,
+    /// Candidate rank for the address (1 = retained match, >1 = Step 2 alternatives).
+    pub rank: u32,
+    /// Runner-up candidate: parcel for Step 2 (address-centric), address for Step 3 (parcel-centric).
+    pub runner_up_id: Option<String>,
+    /// Ranking distance of the runner-up minus ranking distance of the retained candidate.
+    pub margin_m: Option<f32>,
+    /// Building near the address carried by the parcel (`prefer_built_parcels`).
+    pub id_batiment: Option<String>,
+    /// Number of addresses sharing the exact coordinate of the address (stacked default position),
+    /// when at least `stacked_min_addresses`.
+    pub stack_size: Option<u32>,
+    /// BAN `cad_parcelles` value of a PreExisting row, when normalized to a different
+    /// Etalab id (`parcel_id::ParcelIdResolver`).
+    pub id_parcelle_ban: Option<String>,
 }
 
 impl MatchOutput {
@@ -125,6 +280,7 @@ impl MatchOutput {
                 }
             }
             MatchType::FallbackNearest => 50,
+            MatchType::PreExistingFar => 40,
             MatchType::None => 0,
         };
 
@@ -134,10 +290,67 @@ impl MatchOutput {
             match_type,
             distance_m,
             confidence,
+            hint_parcelle: None,
+            rank: 1,
+            runner_up_id: None,
+            margin_m: None,
+            id_batiment: None,
+            stack_size: None,
+            id_parcelle_ban: None,
+        }
+    }
+
+    /// Records the runner-up candidate and lowers confidence when the margin is small.
+    pub fn with_runner_up(mut self, runner_up: Option<(String, f32)>) -> Self {
+        if let Some((id, margin)) = runner_up {
+            let margin = margin.max(0.0);
+            if (margin as f64) < AMBIGUITY_MARGIN_CAP_M {
+                let ratio = 1.0 - margin as f64 / AMBIGUITY_MARGIN_CAP_M;
+                let penalty = (AMBIGUITY_MAX_PENALTY * ratio).round() as u32;
+                self.confidence = self.confidence.saturating_sub(penalty);
+            }
+            self.runner_up_id = Some(id);
+            self.margin_m = Some(margin);
         }
+        self
+    }
+
+    /// `None` row for an address without any parcel; `distance_m` is the distance to the
+    /// nearest parcel, infinite if there is none (written as null by `MatchWriter`).
+    pub fn unmatched(id_ban: String, nearest: Option<(String, f32)>) -> Self {
+        let (hint_parcelle, distance_m) = match nearest {
+            Some((pid, d)) => (Some(pid), d),
+            None => (None, f32::INFINITY),
+        };
+        let mut m = Self::new(id_ban, None, distance_m, MatchType::None);
+        m.hint_parcelle = hint_parcelle;
+        m
     }
 }
 
+/// How Inside / BorderNear treat addresses with a low-precision BAN position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PositionPolicy {
+    /// Low-precision positions are matched like any other.
+    Allow,
+    /// Links are emitted with a lower confidence.
+    Penalize,
+    /// Low-precision positions get no Inside / BorderNear link.
+    Exclude,
+}
+
+/// How a step treats a candidate whose parcel `code_insee` differs from the address `code_insee`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CrossCommunePolicy {
+    /// Cross-commune candidates compete like any other.
+    Allow,
+    /// Cross-commune candidates are ranked with `cross_commune_penalty_m` added to their
+    /// distance, and emitted links get a lower confidence.
+    Penalize,
+    /// Cross-commune candidates are never emitted.
+    Forbid,
+}
+
 #[derive(Debug, Clone)]
 pub struct MatchConfig {
     pub address_max_distance_m: f64,
@@ -145,6 +358,38 @@ pub struct MatchConfig {
     pub fallback_max_distance_m: f64,
     pub fallback_envelope_expand_m: f64,
 
+    // Cross-commune policy per step (PreExisting links are never filtered)
+    pub inside_cross_commune: CrossCommunePolicy,
+    pub border_cross_commune: CrossCommunePolicy,
+    pub fallback_cross_commune: CrossCommunePolicy,
+    /// Ranking penalty (meters) for cross-commune candidates under `Penalize`.
+    pub cross_commune_penalty_m: f64,
+
+    /// Address completion: emit one `None` row per address left without any parcel.
+    pub emit_unmatched_addresses: bool,
+
+    /// PreExisting links farther than this (point→polygon, meters) are demoted to `PreExistingFar`.
+    pub preexisting_max_distance_m: f64,
+
+    /// Optional calibrated scorer; replaces the fixed confidence table for the types it covers.
+    pub confidence_scorer: Option<Arc<dyn ConfidenceScorer>>,
+
+    /// Step 2 candidates kept per address (1 = best only; >1 = candidate mode).
+    pub border_candidates: usize,
+
+    /// BAN `type_position` / `source_position` values treated as low precision.
+    pub low_precision_positions: Vec<String>,
+    pub low_precision_policy: PositionPolicy,
+
+    /// Coordinates shared by at least this many addresses are tagged as stacked (0 = off).
+    pub stacked_min_addresses: usize,
+    /// Stacked addresses are removed from Inside / BorderNear / FallbackNearest candidates.
+    pub exclude_stacked: bool,
+
+    /// Building-aware matching: a parcel carrying a building within `building_max_distance_m`
+    /// of the address is preferred over an unbuilt one (Inside on an empty strip, Step 2 ranking).
+    pub prefer_built_parcels: bool,
+    pub building_max_distance_m: f64,
 }
 
 impl Default for MatchConfig {
@@ -153,6 +398,145 @@ impl Default for MatchConfig {
             address_max_distance_m: 50.0,
             fallback_max_distance_m: 1500.0,
             fallback_envelope_expand_m: 50.0,
+            inside_cross_commune: CrossCommunePolicy::Allow,
+            border_cross_commune: CrossCommunePolicy::Allow,
+            fallback_cross_commune: CrossCommunePolicy::Allow,
+            cross_commune_penalty_m: 50.0,
+            emit_unmatched_addresses: false,
+            preexisting_max_distance_m: 100.0,
+            confidence_scorer: None,
+            border_candidates: 1,
+            low_precision_positions: vec!["segment".to_string()],
+            low_precision_policy: PositionPolicy::Allow,
+            stacked_min_addresses: 10,
+            exclude_stacked: false,
+            prefer_built_parcels: false,
+            building_max_distance_m: 10.0,
+        }
+    }
+}
+
+impl MatchConfig {
+    /// Builder starting from `MatchConfig::default()`, validated by `MatchConfigBuilder::build`.
+    pub fn builder() -> MatchConfigBuilder {
+        MatchConfigBuilder::default()
+    }
+}
+
+/// Builder of `MatchConfig`; values not set keep their default.
+#[derive(Debug, Clone, Default)]
+pub struct MatchConfigBuilder {
+    config: MatchConfig,
+}
+
+impl From<MatchConfig> for MatchConfigBuilder {
+    fn from(config: MatchConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl MatchConfigBuilder {
+    pub fn address_max_distance_m(mut self, meters: f64) -> Self {
+        self.config.address_max_distance_m = meters;
+        self
+    }
+
+    pub fn fallback_max_distance_m(mut self, meters: f64) -> Self {
+        self.config.fallback_max_distance_m = meters;
+        self
+    }
+
+    pub fn fallback_envelope_expand_m(mut self, meters: f64) -> Self {
+        self.config.fallback_envelope_expand_m = meters;
+        self
+    }
+
+    /// Cross-commune policy of Inside, BorderNear and FallbackNearest.
+    pub fn cross_commune(
+        mut self,
+        inside: CrossCommunePolicy,
+        border: CrossCommunePolicy,
+        fallback: CrossCommunePolicy,
+    ) -> Self {
+        self.config.inside_cross_commune = inside;
+        self.config.border_cross_commune = border;
+        self.config.fallback_cross_commune = fallback;
+        self
+    }
+
+    pub fn cross_commune_penalty_m(mut self, meters: f64) -> Self {
+        self.config.cross_commune_penalty_m = meters;
+        self
+    }
+
+    pub fn emit_unmatched_addresses(mut self, emit: bool) -> Self {
+        self.config.emit_unmatched_addresses = emit;
+        self
+    }
+
+    pub fn preexisting_max_distance_m(mut self, meters: f64) -> Self {
+        self.config.preexisting_max_distance_m = meters;
+        self
+    }
+
+    pub fn confidence_scorer(mut self, scorer: Arc<dyn ConfidenceScorer>) -> Self {
+        self.config.confidence_scorer = Some(scorer);
+        self
+    }
+
+    pub fn border_candidates(mut self, candidates: usize) -> Self {
+        self.config.border_candidates = candidates;
+        self
+    }
+
+    /// Low-precision BAN positions and how Inside / BorderNear treat them.
+    pub fn low_precision(
+        mut self,
+        positions: impl IntoIterator<Item = impl Into<String>>,
+        policy: PositionPolicy,
+    ) -> Self {
+        self.config.low_precision_positions = positions.into_iter().map(Into::into).collect();
+        self.config.low_precision_policy = policy;
+        self
+    }
+
+    /// Stacked-address detection threshold (0 = off) and exclusion.
+    pub fn stacked(mut self, min_addresses: usize, exclude: bool) -> Self {
+        self.config.stacked_min_addresses = min_addresses;
+        self.config.exclude_stacked = exclude;
+        self
+    }
+
+    /// Building-aware matching (buildings are given to `Matcher::with_buildings`).
+    pub fn prefer_built_parcels(mut self, max_distance_m: f64) -> Self {
+        self.config.prefer_built_parcels = true;
+        self.config.building_max_distance_m = max_distance_m;
+        self
+    }
+
+    pub fn build(self) -> Result<MatchConfig, Error> {
+        let c = &self.config;
+        for (field, value) in [
+            ("address_max_distance_m", c.address_max_distance_m),
+            ("fallback_max_distance_m", c.fallback_max_distance_m),
+            ("fallback_envelope_expand_m", c.fallback_envelope_expand_m),
+            ("cross_commune_penalty_m", c.cross_commune_penalty_m),
+            ("preexisting_max_distance_m", c.preexisting_max_distance_m),
+            ("building_max_distance_m", c.building_max_distance_m),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(Error::InvalidConfig {
+                    field,
+                    reason: format!("must be a finite distance >= 0 (got {})", value),
+                });
+            }
+        }
+        if c.border_candidates == 0 {
+            return Err(Error::InvalidConfig {
+                field: "border_candidates",
+                reason: "must be >= 1".to_string(),
+            });
         }
+        Ok(self.config)
     }
 }
@@ -0,0 +1,18 @@
+//! Fixtures shared by the unit tests of the library and of the binary (declared in both
+//! `lib.rs` and `main.rs`).
+
+use ban_cadastre::structures::{AddressInput, ParcelData};
+use geo::{point, polygon};
+
+/// Lambert-93 coordinates of its origin (3°E, 46.5°N).
+pub const LAMBERT_93_ORIGIN: (f64, f64) = (700_000.0, 6_600_000.0);
+
+/// Square parcel of side `side` m, lower-left corner at (`x`, `y`).
+pub fn square(id: &str, code_insee: &str, x: f64, y: f64, side: f64) -> ParcelData {
+    let poly = polygon![(x: x, y: y), (x: x + side, y: y), (x: x + side, y: y + side), (x: x, y: y + side)];
+    ParcelData::new(id, code_insee, poly).unwrap()
+}
+
+pub fn address(id: &str, code_insee: &str, x: f64, y: f64) -> AddressInput {
+    AddressInput::new(id, code_insee, point!(x: x, y: y)).unwrap()
+}
@@ -25,8 +25,15 @@ impl MatchWriter {
             Field::new("id_ban", DataType::Utf8, false),
             Field::new("id_parcelle", DataType::Utf8, true),
             Field::new("match_type", DataType::Utf8, false),
-            Field::new("distance_m", DataType::Float32, false),
+            Field::new("distance_m", DataType::Float32, true),
             Field::new("confidence", DataType::UInt32, false),
+            Field::new("hint_parcelle", DataType::Utf8, true),
+            Field::new("rank", DataType::UInt32, false),
+            Field::new("runner_up_id", DataType::Utf8, true),
+            Field::new("margin_m", DataType::Float32, true),
+            Field::new("id_batiment", DataType::Utf8, true),
+            Field::new("stack_size", DataType::UInt32, true),
+            Field::new("id_parcelle_ban", DataType::Utf8, true),
         ]));
 
         let props = WriterProperties::builder().build();
@@ -60,15 +67,30 @@ impl MatchWriter {
         let mut id_ban_builder: Vec<String> = Vec::with_capacity(len);
         let mut id_parcelle_builder: Vec<Option<String>> = Vec::with_capacity(len);
         let mut match_type_builder: Vec<String> = Vec::with_capacity(len);
-        let mut distance_m_builder: Vec<f32> = Vec::with_capacity(len);
+        let mut distance_m_builder: Vec<Option<f32>> = Vec::with_capacity(len);
         let mut confidence_builder: Vec<u32> = Vec::with_capacity(len);
+        let mut hint_parcelle_builder: Vec<Option<String>> = Vec::with_capacity(len);
+        let mut rank_builder: Vec<u32> = Vec::with_capacity(len);
+        let mut runner_up_id_builder: Vec<Option<String>> = Vec::with_capacity(len);
+        let mut margin_m_builder: Vec<Option<f32>> = Vec::with_capacity(len);
+        let mut id_batiment_builder: Vec<Option<String>> = Vec::with_capacity(len);
+        let mut stack_size_builder: Vec<Option<u32>> = Vec::with_capacity(len);
+        let mut id_parcelle_ban_builder: Vec<Option<String>> = Vec::with_capacity(len);
 
         for m in self.batch_buffer.drain(..) {
             id_ban_builder.push(m.id_ban);
             id_parcelle_builder.push(m.id_parcelle);
             match_type_builder.push(m.match_type.to_string());
-            distance_m_builder.push(m.distance_m);
+            // `None` row without any parcel (`MatchOutput::unmatched`)
+            distance_m_builder.push(m.distance_m.is_finite().then_some(m.distance_m));
             confidence_builder.push(m.confidence);
+            hint_parcelle_builder.push(m.hint_parcelle);
+            rank_builder.push(m.rank);
+            runner_up_id_builder.push(m.runner_up_id);
+            margin_m_builder.push(m.margin_m);
+            id_batiment_builder.push(m.id_batiment);
+            stack_size_builder.push(m.stack_size);
+            id_parcelle_ban_builder.push(m.id_parcelle_ban);
         }
 
         let batch = RecordBatch::try_new(
@@ -79,6 +101,13 @@ impl MatchWriter {
                 Arc::new(StringArray::from(match_type_builder)),
                 Arc::new(Float32Array::from(distance_m_builder)),
                 Arc::new(UInt32Array::from(confidence_builder)),
+                Arc::new(StringArray::from(hint_parcelle_builder)),
+                Arc::new(UInt32Array::from(rank_builder)),
+                Arc::new(StringArray::from(runner_up_id_builder)),
+                Arc::new(Float32Array::from(margin_m_builder)),
+                Arc::new(StringArray::from(id_batiment_builder)),
+                Arc::new(UInt32Array::from(stack_size_builder)),
+                Arc::new(StringArray::from(id_parcelle_ban_builder)),
             ],
         )?;
 
@@ -92,3 +121,38 @@ impl MatchWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    #[test]
+    fn unmatched_row_without_parcel_has_a_null_distance() {
+        let path = std::env::temp_dir().join(format!(
+            "ban_cadastre_writer_{}.parquet",
+            std::process::id()
+        ));
+        let mut writer = MatchWriter::new(&path, 10).unwrap();
+        writer
+            .write(MatchOutput::unmatched(
+                "hinted".into(),
+                Some(("P1".into(), 12.5)),
+            ))
+            .unwrap();
+        writer
+            .write(MatchOutput::unmatched("alone".into(), None))
+            .unwrap();
+        writer.close().unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let distances: Vec<Option<f32>> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().get_float(3).ok())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(distances, [Some(12.5), None]);
+    }
+}